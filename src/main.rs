@@ -1,16 +1,29 @@
+use std::path::PathBuf;
+
 mod torus;
 
 fn main() {
+    // `-R` opens every file read-only.
+    let (flags, paths): (Vec<String>, Vec<String>) = std::env::args().skip(1).partition(|arg| arg == "-R");
+    let read_only = !flags.is_empty();
+    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+
     // The main function catches any panics within `run_app_in_raw_mode` 
     // to observe the 'Original mode restored.' message printed by the Drop impl.
     // Without this, the panic handler might exit before the drop message prints,
     // but the mode is still restored before the process terminates.
     let result = std::panic::catch_unwind(|| {
-        torus::terminal_handler::run_app_in_raw_mode();
+        torus::terminal_handler::run_app_in_raw_mode(&paths, read_only)
     });
 
-    if let Err(_err) = result {
-        println!("A panic occurred, but the terminal mode was restored.");
+    match result {
+        Ok(Ok(())) => {}
+        // The terminal has already been restored, so the error reads cleanly.
+        Ok(Err(err)) => {
+            eprintln!("torus: {}", err);
+            std::process::exit(1);
+        }
+        Err(_err) => println!("A panic occurred, but the terminal mode was restored."),
     }
 }
 
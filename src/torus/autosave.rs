@@ -0,0 +1,145 @@
+use std::time::{Duration, Instant};
+
+use super::editor::EditorState;
+
+impl EditorState {
+    /// Whether auto-save is on and no key has been pressed for its delay
+    /// as of `now`.
+    pub fn autosave_due(&self, now: Instant) -> bool {
+        self.config.autosave_ms > 0
+            && now.saturating_duration_since(self.last_activity) >= Duration::from_millis(self.config.autosave_ms)
+    }
+
+    /// Called once per pass of the main loop with whether any keys were
+    /// handled in it. After the auto-save delay without keys, writes every
+    /// modified buffer that has a file name. Files changed on disk are left
+    /// for an explicit save to confirm.
+    pub fn autosave_tick(&mut self, active: bool, now: Instant) {
+        if active {
+            self.last_activity = now;
+        } else if self.autosave_due(now) {
+            self.autosave();
+            // Restart the delay, so a failing save is retried only after
+            // another full pause rather than on every pass.
+            self.last_activity = now;
+        }
+    }
+
+    /// Called when the terminal window loses focus: saves modified buffers
+    /// at once if `autosave_on_focus_lost` is set.
+    pub fn focus_lost(&mut self) {
+        if self.config.autosave_on_focus_lost {
+            self.autosave();
+        }
+    }
+
+    fn autosave(&mut self) {
+        let active = self.active;
+        for index in 0..self.buffers.len() {
+            let buffer = &self.buffers[index];
+            if !buffer.dirty || buffer.filename.is_none() || buffer.read_only {
+                continue;
+            }
+            self.active = index;
+            // Trailing whitespace is left alone, since the pause may be
+            // mid-word on a line still being typed.
+            let result = if self.changed_on_disk() {
+                Err("file changed on disk".to_string())
+            } else {
+                self.write_file().map_err(|err| err.to_string())
+            };
+            if let Err(err) = result {
+                let name = self.filename.as_deref().map(|path| path.display().to_string()).unwrap_or_default();
+                self.set_status_message(format!("Auto-save of {} failed: {}", name, err));
+            }
+        }
+        self.active = active;
+    }
+}
+
+#[cfg(test)]
+mod autosave_tests {
+    use super::*;
+    use crate::torus::input_handler::{Key, handle_key};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("torus-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_idle_detection_waits_for_full_delay() {
+        let mut state = EditorState::with_size(24, 80);
+        state.config.autosave_ms = 500;
+        let start = Instant::now();
+        state.last_activity = start;
+
+        assert!(!state.autosave_due(start + Duration::from_millis(499)));
+        assert!(state.autosave_due(start + Duration::from_millis(500)));
+
+        state.autosave_tick(true, start + Duration::from_millis(450));
+        assert!(!state.autosave_due(start + Duration::from_millis(900)));
+        assert!(state.autosave_due(start + Duration::from_millis(950)));
+
+        state.config.autosave_ms = 0;
+        assert!(!state.autosave_due(start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_idle_tick_saves_named_dirty_buffer() {
+        let path = temp_path("autosave.txt");
+        let mut state = EditorState::with_size(24, 80);
+        state.config.autosave_ms = 100;
+        state.set_filename(path.clone());
+        state.rows = vec!["draft ".to_string()];
+        state.dirty = true;
+        let start = Instant::now();
+        state.last_activity = start;
+
+        state.autosave_tick(false, start + Duration::from_millis(50));
+        assert!(state.dirty);
+
+        state.autosave_tick(false, start + Duration::from_millis(100));
+        assert!(!state.dirty);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "draft \n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_focus_out_saves_only_when_enabled() {
+        let path = temp_path("autosave-focus.txt");
+        let mut state = EditorState::with_size(24, 80);
+        state.set_filename(path.clone());
+        state.rows = vec!["draft".to_string()];
+        state.dirty = true;
+        state.quit_times = 1;
+        let mut input = std::io::empty();
+
+        handle_key(&mut state, Key::FocusOut, &mut input);
+        assert!(state.dirty);
+
+        state.config.autosave_on_focus_lost = true;
+        handle_key(&mut state, Key::FocusOut, &mut input);
+        assert!(!state.dirty);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "draft\n");
+        // A focus change doesn't reset a pending quit confirmation.
+        assert_eq!(state.quit_times, 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unnamed_buffer_is_not_autosaved() {
+        let mut state = EditorState::with_size(24, 80);
+        state.config.autosave_ms = 100;
+        state.rows = vec!["text".to_string()];
+        state.dirty = true;
+        let start = Instant::now();
+        state.last_activity = start;
+
+        state.autosave_tick(false, start + Duration::from_secs(1));
+
+        assert!(state.dirty);
+        assert_eq!(state.last_activity, start + Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,224 @@
+use super::editor::EditorState;
+use super::highlight::Highlight;
+use super::render::cx_to_render_idx;
+use super::undo::EditOp;
+
+/// The bracket pair `c` belongs to as `(open, close)`, and whether `c` is
+/// the opening one, so its match lies ahead of it.
+fn bracket_pair(c: char) -> Option<((char, char), bool)> {
+    match c {
+        '(' | ')' => Some((('(', ')'), c == '(')),
+        '[' | ']' => Some((('[', ']'), c == '[')),
+        '{' | '}' => Some((('{', '}'), c == '{')),
+        _ => None,
+    }
+}
+
+/// Character auto-inserted after typing `c`, if `c` opens a pair.
+fn closing_char(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' | '\'' => Some(c),
+        _ => None,
+    }
+}
+
+impl EditorState {
+    /// Inserts a typed character. With `auto_close_brackets` set, an
+    /// opening bracket or quote also gets its closing counterpart after
+    /// the cursor, unless text follows directly, and typing a closing one
+    /// that is already next just steps over it.
+    pub fn type_char(&mut self, c: char) {
+        if !self.config.auto_close_brackets {
+            self.insert_char(c);
+            return;
+        }
+
+        let (y, x) = (self.cursor_y, self.cursor_x.min(self.row_len(self.cursor_y)));
+        let next = self.row(y).and_then(|row| row.chars().nth(x));
+        let closes = matches!(c, ')' | ']' | '}' | '"' | '\'');
+        if closes && next == Some(c) {
+            self.cursor_x = x + 1;
+            return;
+        }
+
+        match closing_char(c) {
+            Some(close) if !self.overwrite && next.is_none_or(char::is_whitespace) => {
+                let mut ops = Vec::new();
+                if y == self.num_rows() {
+                    ops.push(EditOp::InsertRow { row: y, text: String::new() });
+                }
+                ops.push(EditOp::InsertChar { at: (y, x), c });
+                ops.push(EditOp::InsertChar { at: (y, x + 1), c: close });
+                self.edit_all(ops, (y, x + 1));
+            }
+            _ => self.insert_char(c),
+        }
+    }
+
+    /// Position of the bracket matching the one under the cursor, as
+    /// `(row, col)`. Brackets inside strings and comments only match each
+    /// other, so a `)` in a string literal doesn't close a call. Returns
+    /// `None` if the cursor is not on a bracket or it is unbalanced.
+    pub fn match_bracket(&self) -> Option<(usize, usize)> {
+        let (y, x) = (self.cursor_y, self.cursor_x);
+        let c = self.row(y)?.chars().nth(x)?;
+        let ((open, close), forward) = bracket_pair(c)?;
+        let (this, other) = if forward { (open, close) } else { (close, open) };
+        let literal = self.is_literal(y, x);
+
+        let mut depth = 0;
+        let mut visit = |c: char, y: usize, x: usize| {
+            if self.is_literal(y, x) != literal {
+                return false;
+            }
+            if c == this {
+                depth += 1;
+            } else if c == other {
+                depth -= 1;
+            }
+            depth == 0
+        };
+
+        if forward {
+            for row_y in y..self.num_rows() {
+                let from = if row_y == y { x } else { 0 };
+                for (col, c) in self.rows[row_y].chars().enumerate().skip(from) {
+                    if visit(c, row_y, col) {
+                        return Some((row_y, col));
+                    }
+                }
+            }
+        } else {
+            for row_y in (0..=y).rev() {
+                let chars: Vec<char> = self.rows[row_y].chars().collect();
+                let to = if row_y == y { x + 1 } else { chars.len() };
+                for col in (0..to).rev() {
+                    if visit(chars[col], row_y, col) {
+                        return Some((row_y, col));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Moves the cursor to the bracket matching the one under it.
+    pub fn jump_to_matching_bracket(&mut self) {
+        match self.match_bracket() {
+            Some((y, x)) => (self.cursor_y, self.cursor_x) = (y, x),
+            None => self.set_status_message("No matching bracket".to_string()),
+        }
+    }
+
+    /// Whether the char at `(y, x)` is highlighted as part of a string or
+    /// comment. False while the row's highlight has not been computed.
+    fn is_literal(&self, y: usize, x: usize) -> bool {
+        let (Some(row), Some(hl)) = (self.row(y), self.highlights.get(y)) else {
+            return false;
+        };
+        let idx = cx_to_render_idx(row, x, self.config.tab_width);
+        matches!(hl.hl.get(idx), Some(Highlight::String | Highlight::Comment))
+    }
+}
+
+#[cfg(test)]
+mod bracket_tests {
+    use super::*;
+
+    fn state_with(lines: &[&str]) -> EditorState {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = lines.iter().map(|line| line.to_string()).collect();
+        state
+    }
+
+    #[test]
+    fn test_match_nested_brackets() {
+        let mut state = state_with(&["((()))"]);
+
+        state.cursor_x = 0;
+        assert_eq!(state.match_bracket(), Some((0, 5)));
+        state.cursor_x = 1;
+        assert_eq!(state.match_bracket(), Some((0, 4)));
+        state.cursor_x = 3;
+        assert_eq!(state.match_bracket(), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_match_across_lines() {
+        let mut state = state_with(&["fn f() {", "    [1]", "}"]);
+        state.cursor_x = 7;
+
+        state.jump_to_matching_bracket();
+        assert_eq!((state.cursor_y, state.cursor_x), (2, 0));
+        state.jump_to_matching_bracket();
+        assert_eq!((state.cursor_y, state.cursor_x), (0, 7));
+    }
+
+    #[test]
+    fn test_unmatched_bracket_reports_status() {
+        let mut state = state_with(&["(()"]);
+
+        assert_eq!(state.match_bracket(), None);
+        state.jump_to_matching_bracket();
+
+        assert_eq!((state.cursor_y, state.cursor_x), (0, 0));
+        assert_eq!(state.status_msg, "No matching bracket");
+    }
+
+    #[test]
+    fn test_brackets_in_strings_are_skipped() {
+        let mut state = state_with(&["f(\")\")"]);
+        state.set_filename(std::path::PathBuf::from("main.rs"));
+        state.update_highlights();
+
+        assert_eq!(state.match_bracket(), None);
+        state.cursor_x = 1;
+        assert_eq!(state.match_bracket(), Some((0, 5)));
+    }
+
+    #[test]
+    fn test_auto_close_inserts_pair() {
+        let mut state = state_with(&["f x"]);
+        state.config.auto_close_brackets = true;
+        state.cursor_x = 1;
+
+        state.type_char('(');
+        assert_eq!(state.rows, vec!["f() x"]);
+        assert_eq!(state.cursor_x, 2);
+
+        // Not before text, so a quote can wrap an existing word.
+        state.cursor_x = 4;
+        state.type_char('"');
+        assert_eq!(state.rows, vec!["f() \"x"]);
+
+        state.undo();
+        state.undo();
+        assert_eq!(state.rows, vec!["f x"]);
+    }
+
+    #[test]
+    fn test_auto_close_steps_over_closing_char() {
+        let mut state = EditorState::with_size(24, 80);
+        state.config.auto_close_brackets = true;
+
+        for c in "[\"a\"]".chars() {
+            state.type_char(c);
+        }
+
+        assert_eq!(state.rows, vec!["[\"a\"]"]);
+        assert_eq!(state.cursor_x, 5);
+    }
+
+    #[test]
+    fn test_without_auto_close_brackets_are_typed_plainly() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.type_char('{');
+        state.type_char('}');
+
+        assert_eq!(state.rows, vec!["{}"]);
+    }
+}
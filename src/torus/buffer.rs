@@ -0,0 +1,202 @@
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use super::highlight::{highlight_row, RowHighlight, SyntaxDef};
+use super::jumplist::JumpList;
+use super::render::render_row;
+use super::swap::SwapStatus;
+use super::undo::{EditOp, UndoStack};
+
+/// How rows are terminated when the buffer is written to disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// The ending used by most lines of `text`; ties go to LF.
+    pub fn detect(text: &str) -> LineEnding {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf { LineEnding::Crlf } else { LineEnding::Lf }
+    }
+
+    /// The terminator written after each row.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// One open file: its text plus everything that belongs to it rather than
+/// to the editor as a whole, so switching buffers restores the cursor,
+/// scroll position and undo history.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub row_offset: usize,
+    pub col_offset: usize,
+    pub rows: Vec<String>,
+    pub filename: Option<PathBuf>,
+    /// Name shown for a buffer that holds something other than a file,
+    /// like build output.
+    pub title: Option<&'static str>,
+    /// Line ending the file was loaded with, reused when saving it.
+    pub line_ending: LineEnding,
+    /// Set when the loaded file's last line had no terminator, so saving
+    /// leaves it off too.
+    pub no_final_newline: bool,
+    /// Modification time of the file when it was last loaded or saved.
+    pub mtime: Option<SystemTime>,
+    /// Highlighting rules picked from the file name, if any match.
+    pub syntax: Option<&'static SyntaxDef>,
+    /// Cached highlight of each row, kept in step with `rows` by
+    /// `update_highlights`.
+    pub highlights: Vec<RowHighlight>,
+    /// Rows whose cached highlight is stale. Rows after them are only
+    /// re-highlighted if the block-comment state carried into them changes.
+    hl_dirty: Option<Range<usize>>,
+    /// History of edits for undo and redo.
+    pub undo_stack: UndoStack,
+    /// Positions left by big jumps, for going back to them.
+    pub jumps: JumpList,
+    /// Fixed end of the selection as `(row, col)`; the cursor is the other end.
+    pub selection_anchor: Option<(usize, usize)>,
+    /// Set by every edit and cleared once the buffer is saved.
+    pub dirty: bool,
+    /// Refuses every edit, for viewing a file without risk of changing it.
+    pub read_only: bool,
+    /// Whether the file's swap file is this buffer's to write.
+    pub swap: SwapStatus,
+}
+
+impl Buffer {
+    /// True for an unnamed, empty, unmodified buffer that opening a file
+    /// can reuse instead of adding another.
+    pub fn is_scratch(&self) -> bool {
+        self.filename.is_none() && self.title.is_none() && self.rows.is_empty() && !self.dirty
+    }
+
+    /// Name shown in the status bar: the file name, or the title of a
+    /// buffer that isn't a file.
+    pub fn display_name(&self) -> String {
+        match &self.filename {
+            Some(path) => path.display().to_string(),
+            None => self.title.unwrap_or("[No Name]").to_string(),
+        }
+    }
+
+    /// Marks the cached highlight of row `from` and everything after it as
+    /// stale, e.g. after a change of syntax or tab width.
+    pub fn invalidate_highlight(&mut self, from: usize) {
+        self.mark_highlight_dirty(from..usize::MAX);
+    }
+
+    fn mark_highlight_dirty(&mut self, rows: Range<usize>) {
+        self.hl_dirty = Some(match self.hl_dirty.take() {
+            Some(dirty) => dirty.start.min(rows.start)..dirty.end.max(rows.end),
+            None => rows,
+        });
+    }
+
+    /// Keeps the highlight cache in step with `op`, which has just been
+    /// applied to `rows`: cache entries are added or dropped along with
+    /// rows, and only the rows `op` touched are marked stale.
+    pub fn note_edit(&mut self, op: &EditOp) {
+        match *op {
+            EditOp::InsertChar { at: (y, _), .. } | EditOp::DeleteChar { at: (y, _), .. } => {
+                self.mark_highlight_dirty(y..y + 1);
+            }
+            EditOp::SplitLine { at: (y, _) } => {
+                self.highlight_row_inserted(y + 1);
+                self.mark_highlight_dirty(y..y + 2);
+            }
+            EditOp::JoinLine { at: (y, _) } => {
+                self.highlight_row_removed(y + 1);
+                self.mark_highlight_dirty(y..y + 1);
+            }
+            EditOp::InsertRow { row, .. } => {
+                self.highlight_row_inserted(row);
+                self.mark_highlight_dirty(row..row + 1);
+            }
+            // The row that moved up may now follow a different comment state.
+            EditOp::RemoveRow { row, .. } => {
+                self.highlight_row_removed(row);
+                self.mark_highlight_dirty(row..row + 1);
+            }
+        }
+    }
+
+    /// Makes room in the cache for a row inserted at `at`.
+    fn highlight_row_inserted(&mut self, at: usize) {
+        if self.highlights.len() + 1 != self.rows.len() {
+            // Already out of step; `update_highlights` rebuilds it all.
+            self.highlights.clear();
+            return;
+        }
+        self.highlights.insert(at, RowHighlight::default());
+        if let Some(dirty) = &mut self.hl_dirty {
+            if dirty.start >= at {
+                dirty.start += 1;
+            }
+            if dirty.end > at {
+                dirty.end = dirty.end.saturating_add(1);
+            }
+        }
+    }
+
+    /// Drops the cache entry of a row removed from `at`.
+    fn highlight_row_removed(&mut self, at: usize) {
+        if self.highlights.len() != self.rows.len() + 1 {
+            self.highlights.clear();
+            return;
+        }
+        self.highlights.remove(at);
+        if let Some(dirty) = &mut self.hl_dirty {
+            if dirty.start > at {
+                dirty.start -= 1;
+            }
+            if dirty.end > at && dirty.end != usize::MAX {
+                dirty.end -= 1;
+            }
+        }
+    }
+
+    /// Re-highlights stale rows, expanding tabs to `tab_stop` columns, and
+    /// carries on past them only while the block-comment state they hand
+    /// to the next row differs from before. If rows were replaced
+    /// wholesale, the whole cache is rebuilt. Returns how many rows were
+    /// highlighted.
+    pub fn update_highlights(&mut self, tab_stop: usize) -> usize {
+        if self.highlights.len() != self.rows.len() {
+            self.highlights = vec![RowHighlight::default(); self.rows.len()];
+            self.hl_dirty = Some(0..usize::MAX);
+        }
+        let Some(dirty) = self.hl_dirty.take() else {
+            return 0;
+        };
+        if dirty.start >= self.rows.len() {
+            return 0;
+        }
+
+        let mut y = dirty.start;
+        let mut in_comment = y > 0 && self.highlights[y - 1].open_comment;
+        while y < self.rows.len() {
+            let hl = highlight_row(&render_row(&self.rows[y], tab_stop), self.syntax, in_comment);
+            let carry_changed = hl.open_comment != self.highlights[y].open_comment;
+            in_comment = hl.open_comment;
+            self.highlights[y] = hl;
+            y += 1;
+            if y >= dirty.end && !carry_changed {
+                break;
+            }
+        }
+        y - dirty.start
+    }
+}
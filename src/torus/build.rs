@@ -0,0 +1,319 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+
+use super::buffer::Buffer;
+use super::editor::EditorState;
+use super::filter::shell;
+
+/// Title of the read-only buffer holding the last build's output.
+pub const BUILD_TITLE: &str = "[Build]";
+
+/// Runs `command` through the shell and waits for it, returning what it
+/// wrote to stdout and stderr interleaved as it wrote them.
+pub fn capture_output(command: &str) -> io::Result<(String, ExitStatus)> {
+    let (mut reader, writer) = io::pipe()?;
+    let mut child = shell(command)
+        .stdin(Stdio::null())
+        .stdout(writer.try_clone()?)
+        .stderr(writer)
+        .spawn()?;
+
+    // The command's copies of the pipe are gone once it exits, which ends
+    // the read.
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output)?;
+    let status = child.wait()?;
+    Ok((String::from_utf8_lossy(&output).into_owned(), status))
+}
+
+/// A place in a file named by a line of compiler or tool output. Lines and
+/// columns are 1-based, as tools print them.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: Option<usize>,
+}
+
+/// Finds the first `path:line[:col]` in `text`, also accepting MSVC's
+/// `path(line,col)` and Python's `File "path", line N`.
+pub fn parse_location(text: &str) -> Option<Location> {
+    if let Some(rest) = text.trim_start().strip_prefix("File \"") {
+        let (path, rest) = rest.split_once('"')?;
+        let line = rest.strip_prefix(", line ")?;
+        let line = line.split(|c: char| !c.is_ascii_digit()).next()?;
+        return location(path, line, None);
+    }
+    text.split_whitespace().find_map(parse_location_word)
+}
+
+/// Parses one whitespace-separated word as `path:line[:col]` or
+/// `path(line[,col])`, ignoring punctuation around it.
+fn parse_location_word(word: &str) -> Option<Location> {
+    let word = word.trim_matches(|c| matches!(c, '"' | '\'' | '[' | ']' | ',' | ':'));
+    if let Some((path, rest)) = word.split_once('(') {
+        let numbers = rest.split_once(')')?.0;
+        let mut numbers = numbers.split(',');
+        return location(path, numbers.next()?, numbers.next());
+    }
+
+    // Keep a Windows drive letter with the rest of its path.
+    let (drive, rest) = match word.split_once(':') {
+        Some((drive, rest)) if drive.len() == 1 && rest.starts_with(['\\', '/']) => (word.get(..2)?, rest),
+        _ => ("", word),
+    };
+    let mut parts = rest.split(':');
+    let path = format!("{}{}", drive, parts.next()?);
+    location(&path, parts.next()?, parts.next())
+}
+
+fn location(path: &str, line: &str, col: Option<&str>) -> Option<Location> {
+    // A run of digits is more likely a time or a count than a file name.
+    if path.is_empty() || path.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let line = line.parse().ok().filter(|&line| line > 0)?;
+    let col = col.and_then(|col| col.parse().ok()).filter(|&col| col > 0);
+    Some(Location { path: PathBuf::from(path), line, col })
+}
+
+/// True if `a` and `b` name the same existing file, however they spell it.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+impl EditorState {
+    /// Runs the configured build command and shows its output in the
+    /// build buffer. The command runs to completion before the editor
+    /// takes more keys, so "Running" is drawn first.
+    pub fn build(&mut self) {
+        let command = self.config.build_command.clone();
+        self.set_status_message(format!("Running {}...", command));
+        let _ = self.refresh_screen();
+
+        match capture_output(&command) {
+            Ok((output, status)) => {
+                self.show_build_output(&output);
+                let result = if status.success() {
+                    "succeeded".to_string()
+                } else {
+                    format!("failed ({})", status)
+                };
+                self.set_status_message(format!("{} {} | Alt-O = back", command, result));
+            }
+            Err(err) => self.set_status_message(format!("Can't run {}: {}", command, err)),
+        }
+    }
+
+    /// Replaces the build buffer's text with `output`, one row per line,
+    /// adding the buffer if there isn't one yet, and switches to it.
+    pub fn show_build_output(&mut self, output: &str) {
+        let previous = self.active;
+        match self.build_buffer() {
+            Some(index) => self.active = index,
+            None => self.add_buffer(),
+        }
+        if previous != self.active {
+            self.build_return = Some(previous);
+        }
+
+        // A fresh buffer drops the old output's cursor, highlights and undo.
+        let mut buffer = Buffer::default();
+        buffer.title = Some(BUILD_TITLE);
+        buffer.rows = output.lines().map(str::to_string).collect();
+        buffer.read_only = true;
+        **self = buffer;
+    }
+
+    /// Switches between the build output and the buffer it was opened
+    /// from. Rings the bell if nothing has been built yet.
+    pub fn toggle_build_output(&mut self) {
+        let Some(build) = self.build_buffer() else {
+            self.ring_bell();
+            return;
+        };
+        if self.active != build {
+            self.build_return = Some(self.active);
+            self.active = build;
+        } else if let Some(previous) = self
+            .build_return
+            .filter(|&i| i < self.buffers.len() && i != build)
+        {
+            self.active = previous;
+        } else {
+            self.ring_bell();
+        }
+    }
+
+    /// Opens the place named by the cursor line, as Enter does in the build
+    /// output. Rings the bell if the line names none.
+    pub fn open_location_at_cursor(&mut self) {
+        match self.row(self.cursor_y).and_then(parse_location) {
+            Some(location) => self.open_location(&location),
+            None => self.ring_bell(),
+        }
+    }
+
+    /// Switches to the buffer for `location`'s file, opening it if it isn't
+    /// open yet, and puts the cursor on its line and column. Relative paths
+    /// are taken from the directory the editor was started in.
+    pub fn open_location(&mut self, location: &Location) {
+        let path = &location.path;
+        if !path.is_file() {
+            self.set_status_message(format!("No such file: {}", path.display()));
+            return;
+        }
+
+        let open = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.filename.as_deref().is_some_and(|name| same_file(name, path)));
+        match open {
+            Some(index) => self.active = index,
+            None => {
+                self.open_in_new_buffer(path);
+                if self.filename.as_deref() != Some(path.as_path()) {
+                    return;
+                }
+            }
+        }
+
+        self.goto_line(location.line);
+        self.cursor_x = location.col.map_or(0, |col| col - 1).min(self.row_len(self.cursor_y));
+    }
+
+    fn build_buffer(&self) -> Option<usize> {
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.title == Some(BUILD_TITLE))
+    }
+}
+
+#[cfg(test)]
+mod build_tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("torus-{}-{}", std::process::id(), name))
+    }
+
+    fn at(path: &str, line: usize, col: Option<usize>) -> Option<Location> {
+        Some(Location { path: PathBuf::from(path), line, col })
+    }
+
+    #[test]
+    fn test_parse_compiler_output_formats() {
+        // gcc and clang
+        assert_eq!(parse_location("src/main.c:42:5: error: expected ';'"), at("src/main.c", 42, Some(5)));
+        // rustc
+        assert_eq!(parse_location("  --> src/main.rs:7:13"), at("src/main.rs", 7, Some(13)));
+        // grep -n
+        assert_eq!(parse_location("lib/util.py:12:def main():"), at("lib/util.py", 12, None));
+        // Python tracebacks
+        assert_eq!(parse_location("  File \"app/run.py\", line 88, in <module>"), at("app/run.py", 88, None));
+        // MSVC
+        assert_eq!(parse_location("src\\main.cpp(42,5): error C2143"), at("src\\main.cpp", 42, Some(5)));
+        assert_eq!(parse_location("C:\\work\\main.c:3:1: warning"), at("C:\\work\\main.c", 3, Some(1)));
+    }
+
+    #[test]
+    fn test_parse_rejects_lines_without_locations() {
+        assert_eq!(parse_location("error: could not compile `torus-edit`"), None);
+        assert_eq!(parse_location("Finished at 12:30:45"), None);
+        assert_eq!(parse_location("main.rs:0:1"), None);
+        assert_eq!(parse_location(""), None);
+    }
+
+    #[test]
+    fn test_enter_on_build_line_opens_file_at_line() {
+        let path = temp_path("build-location.rs");
+        fs::write(&path, "fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+        state.show_build_output(&format!("error: oops\n --> {}:2:4\n", path.display()));
+        state.cursor_y = 1;
+
+        state.open_location_at_cursor();
+
+        assert_eq!(state.filename.as_deref(), Some(path.as_path()));
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 3));
+
+        // A second jump reuses the open buffer.
+        state.toggle_build_output();
+        state.open_location_at_cursor();
+        assert_eq!(state.buffers.len(), 3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file_is_reported() {
+        let mut state = EditorState::with_size(24, 80);
+        state.show_build_output("nowhere/missing.rs:3:1: error\n");
+
+        state.open_location_at_cursor();
+
+        assert_eq!(state.title, Some(BUILD_TITLE));
+        assert_eq!(state.status_msg, "No such file: nowhere/missing.rs");
+    }
+
+    #[test]
+    fn test_output_lines_go_to_a_new_read_only_buffer() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["fn main() {}".to_string()];
+        state.cursor_x = 3;
+
+        state.show_build_output("error: oops\n  --> main.rs:1:4\n");
+
+        assert_eq!(state.buffers.len(), 2);
+        assert_eq!(state.rows, vec!["error: oops", "  --> main.rs:1:4"]);
+        assert!(state.read_only);
+        assert_eq!(state.title, Some(BUILD_TITLE));
+
+        state.toggle_build_output();
+        assert_eq!(state.active, 0);
+        assert_eq!(state.rows, vec!["fn main() {}"]);
+        assert_eq!(state.cursor_x, 3);
+
+        state.toggle_build_output();
+        assert_eq!(state.active, 1);
+    }
+
+    #[test]
+    fn test_rebuilding_reuses_the_build_buffer() {
+        let mut state = EditorState::with_size(24, 80);
+        state.show_build_output("first\n");
+        state.cursor_y = 0;
+        state.toggle_build_output();
+
+        state.show_build_output("second\nthird\n");
+
+        assert_eq!(state.buffers.len(), 2);
+        assert_eq!(state.rows, vec!["second", "third"]);
+        state.toggle_build_output();
+        assert_eq!(state.active, 0);
+    }
+
+    #[test]
+    fn test_toggle_without_build_rings_bell() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.toggle_build_output();
+
+        assert_eq!(state.active, 0);
+        assert!(state.bell_pending);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_capture_interleaves_stdout_and_stderr() {
+        let (output, status) =
+            capture_output("echo one; echo two >&2; echo three; exit 2").unwrap();
+
+        assert_eq!(output, "one\ntwo\nthree\n");
+        assert_eq!(status.code(), Some(2));
+    }
+}
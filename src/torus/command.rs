@@ -0,0 +1,230 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+use super::editor::EditorState;
+
+/// An ex-style command typed at the `:` prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `:w` - save the active buffer.
+    Write,
+    /// `:q` - quit, subject to the unsaved-changes warning.
+    Quit,
+    /// `:wq` - save, then quit if the save worked.
+    WriteQuit,
+    /// `:e <file>` - open a file in a new buffer.
+    Edit(PathBuf),
+    /// `:set <option>` - change a setting.
+    Set(String),
+    /// `:recover` - restore the text saved in the file's swap file.
+    Recover,
+    /// `:discard` - delete the file's swap file without restoring it.
+    Discard,
+    /// `:bd` - close the active buffer, or with `:bd!` even if it has
+    /// unsaved changes.
+    CloseBuffer { force: bool },
+    /// `:wc` - count the lines, words, chars and bytes of the selection, or
+    /// of the whole buffer.
+    WordCount,
+}
+
+/// Outcome of running a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandResult {
+    Done,
+    /// The command could not be run; the message says why.
+    Failed(String),
+}
+
+/// Parses a command line, with or without its leading `:`.
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let line = line.trim().trim_start_matches(':');
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (line, ""),
+    };
+
+    match (name, arg) {
+        ("w", "") => Ok(Command::Write),
+        ("q", "") => Ok(Command::Quit),
+        ("wq" | "x", "") => Ok(Command::WriteQuit),
+        ("e", "") => Err("Usage: :e <file>".to_string()),
+        ("e", file) => Ok(Command::Edit(PathBuf::from(file))),
+        ("set", "") => Err("Usage: :set <option>".to_string()),
+        ("set", option) => Ok(Command::Set(option.to_string())),
+        ("recover", "") => Ok(Command::Recover),
+        ("discard", "") => Ok(Command::Discard),
+        ("bd", "") => Ok(Command::CloseBuffer { force: false }),
+        ("bd!", "") => Ok(Command::CloseBuffer { force: true }),
+        ("wc", "") => Ok(Command::WordCount),
+        _ => Err(format!("Not a command: {}", line)),
+    }
+}
+
+impl EditorState {
+    /// Asks for a command at a `:` prompt and runs it.
+    pub fn command_with_prompt<R: Read>(&mut self, input: &mut R) {
+        if let Some(line) = self.prompt(input, ":%s", |_, _, _| {}) {
+            self.run_command(&line);
+        }
+    }
+
+    /// Parses and runs a command line. Failures are also reported in the
+    /// message bar.
+    pub fn run_command(&mut self, line: &str) -> CommandResult {
+        let result = match parse_command(line) {
+            Ok(command) => self.execute(command),
+            Err(msg) => CommandResult::Failed(msg),
+        };
+        if let CommandResult::Failed(msg) = &result {
+            self.set_status_message(msg.clone());
+        }
+        result
+    }
+
+    fn execute(&mut self, command: Command) -> CommandResult {
+        match command {
+            Command::Write => self.write_command(),
+            Command::Quit => {
+                self.request_quit();
+                CommandResult::Done
+            }
+            Command::WriteQuit => {
+                let result = self.write_command();
+                if result == CommandResult::Done {
+                    self.request_quit();
+                }
+                result
+            }
+            Command::Edit(path) => {
+                self.open_in_new_buffer(&path);
+                CommandResult::Done
+            }
+            Command::Set(option) => self.set_option(&option),
+            Command::Recover => self.recover_swap().map_or_else(CommandResult::Failed, |()| CommandResult::Done),
+            Command::Discard => self.discard_swap().map_or_else(CommandResult::Failed, |()| CommandResult::Done),
+            Command::CloseBuffer { force } => {
+                if self.dirty && !force {
+                    return CommandResult::Failed("File has unsaved changes (:bd! to close anyway)".to_string());
+                }
+                self.remove_active_buffer();
+                CommandResult::Done
+            }
+            Command::WordCount => {
+                self.show_stats();
+                CommandResult::Done
+            }
+        }
+    }
+
+    /// Saves the active buffer for `:w`.
+    fn write_command(&mut self) -> CommandResult {
+        if self.filename.is_none() {
+            return CommandResult::Failed("No file name".to_string());
+        }
+        match self.save() {
+            Ok(len) => {
+                self.remember_positions();
+                self.set_status_message(format!("{} bytes written to disk", len));
+                CommandResult::Done
+            }
+            Err(err) => CommandResult::Failed(format!("Can't save! I/O error: {}", err)),
+        }
+    }
+
+    /// Applies a `:set` option.
+    fn set_option(&mut self, option: &str) -> CommandResult {
+        match option {
+            "number" | "nu" => self.show_line_numbers = true,
+            "nonumber" | "nonu" => self.show_line_numbers = false,
+            "relativenumber" | "rnu" => self.relative_line_numbers = true,
+            "norelativenumber" | "nornu" => self.relative_line_numbers = false,
+            "list" => self.show_whitespace = true,
+            "nolist" => self.show_whitespace = false,
+            "wrap" => self.wrap_lines = true,
+            "nowrap" => self.wrap_lines = false,
+            "cursorline" | "cul" => self.highlight_current_line = true,
+            "nocursorline" | "nocul" => self.highlight_current_line = false,
+            _ => return CommandResult::Failed(format!("Unknown option: {}", option)),
+        }
+        CommandResult::Done
+    }
+}
+
+#[cfg(test)]
+mod command_tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(parse_command(":w"), Ok(Command::Write));
+        assert_eq!(parse_command("q"), Ok(Command::Quit));
+        assert_eq!(parse_command(":wq"), Ok(Command::WriteQuit));
+        assert_eq!(parse_command(":e  src/main.rs "), Ok(Command::Edit(PathBuf::from("src/main.rs"))));
+        assert_eq!(parse_command(":set number"), Ok(Command::Set("number".to_string())));
+        assert_eq!(parse_command(":recover"), Ok(Command::Recover));
+        assert_eq!(parse_command(":bd!"), Ok(Command::CloseBuffer { force: true }));
+        assert_eq!(parse_command(":wc"), Ok(Command::WordCount));
+        assert_eq!(parse_command(":frob"), Err("Not a command: frob".to_string()));
+        assert!(parse_command(":e").is_err());
+    }
+
+    #[test]
+    fn test_write_command_saves_buffer() {
+        let path = std::env::temp_dir().join(format!("torus-{}-command-w.txt", std::process::id()));
+        let mut state = EditorState::with_size(24, 80);
+        state.filename = Some(path.clone());
+        state.insert_char('a');
+
+        let result = state.run_command(":w");
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(result, CommandResult::Done);
+        assert_eq!(saved, "a\n");
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn test_quit_command_quits_clean_editor() {
+        let mut state = EditorState::with_size(24, 80);
+
+        assert_eq!(state.run_command(":q"), CommandResult::Done);
+        assert!(state.quit);
+    }
+
+    #[test]
+    fn test_close_buffer_command_refuses_unsaved_changes() {
+        let mut state = EditorState::with_size(24, 80);
+        state.add_buffer();
+        state.insert_char('a');
+
+        assert!(matches!(state.run_command(":bd"), CommandResult::Failed(_)));
+        assert_eq!(state.buffers.len(), 2);
+
+        assert_eq!(state.run_command(":bd!"), CommandResult::Done);
+        assert_eq!(state.buffers.len(), 1);
+        assert!(!state.quit);
+    }
+
+    #[test]
+    fn test_unknown_command_is_reported() {
+        let mut state = EditorState::with_size(24, 80);
+
+        let result = state.run_command(":frob 3");
+
+        assert_eq!(result, CommandResult::Failed("Not a command: frob 3".to_string()));
+        assert_eq!(state.status_msg, "Not a command: frob 3");
+        assert!(!state.quit);
+    }
+
+    #[test]
+    fn test_set_number() {
+        let mut state = EditorState::with_size(24, 80);
+
+        assert_eq!(state.run_command(":set number"), CommandResult::Done);
+        assert!(state.show_line_numbers);
+        assert!(matches!(state.run_command(":set bogus"), CommandResult::Failed(_)));
+    }
+}
@@ -0,0 +1,99 @@
+use super::editor::EditorState;
+use super::undo::EditOp;
+
+/// Edits that toggle a `prefix` line comment on row `y`, whose text is
+/// `row`: the prefix and a space are added after the indentation, or an
+/// existing prefix is removed along with one space after it. Also returns
+/// the column the change starts at and how many chars it adds (positive)
+/// or removes (negative).
+pub fn toggle_line_comment(row: &str, y: usize, prefix: &str) -> (Vec<EditOp>, usize, isize) {
+    let indent = row.chars().take_while(|&c| c == ' ' || c == '\t').count();
+    let rest: String = row.chars().skip(indent).collect();
+
+    if let Some(after) = rest.strip_prefix(prefix) {
+        let mut removed: Vec<char> = prefix.chars().collect();
+        if after.starts_with(' ') {
+            removed.push(' ');
+        }
+        let ops = removed.iter().map(|&c| EditOp::DeleteChar { at: (y, indent), c }).collect();
+        (ops, indent, -(removed.len() as isize))
+    } else {
+        let added: Vec<char> = prefix.chars().chain([' ']).collect();
+        let ops = added
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| EditOp::InsertChar { at: (y, indent + i), c })
+            .collect();
+        (ops, indent, added.len() as isize)
+    }
+}
+
+impl EditorState {
+    /// Comments out the cursor line with the language's line comment
+    /// prefix, or uncomments it if it already is. Rings the bell for
+    /// languages without line comments.
+    pub fn toggle_comment(&mut self) {
+        let prefix = self.syntax.and_then(|syntax| syntax.line_comment);
+        let (Some(prefix), Some(row)) = (prefix, self.row(self.cursor_y)) else {
+            self.ring_bell();
+            return;
+        };
+
+        let y = self.cursor_y;
+        let (ops, at, delta) = toggle_line_comment(row, y, prefix);
+        // The cursor keeps its place in the text, or lands where a removed
+        // prefix was.
+        let x = if self.cursor_x < at { self.cursor_x } else { self.cursor_x.saturating_add_signed(delta).max(at) };
+        self.edit_all(ops, (y, x));
+    }
+}
+
+#[cfg(test)]
+mod comment_tests {
+    use super::*;
+    use crate::torus::highlight::select_syntax;
+
+    #[test]
+    fn test_toggle_comment_on_rust_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.syntax = select_syntax("x.rs");
+        state.rows = vec!["let x = 1;".to_string()];
+        state.cursor_x = 4;
+
+        state.toggle_comment();
+        assert_eq!(state.rows, vec!["// let x = 1;"]);
+        assert_eq!(state.cursor_x, 7);
+
+        state.toggle_comment();
+        assert_eq!(state.rows, vec!["let x = 1;"]);
+        assert_eq!(state.cursor_x, 4);
+    }
+
+    #[test]
+    fn test_toggle_comment_keeps_indentation() {
+        let mut state = EditorState::with_size(24, 80);
+        state.syntax = select_syntax("x.rs");
+        state.rows = vec!["    foo();".to_string(), "\t//bar();".to_string()];
+
+        state.toggle_comment();
+        assert_eq!(state.rows[0], "    // foo();");
+
+        state.cursor_y = 1;
+        state.toggle_comment();
+        assert_eq!(state.rows[1], "\tbar();");
+
+        state.undo();
+        assert_eq!(state.rows[1], "\t//bar();");
+    }
+
+    #[test]
+    fn test_toggle_comment_without_syntax_rings_bell() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["text".to_string()];
+
+        state.toggle_comment();
+
+        assert_eq!(state.rows, vec!["text"]);
+        assert!(state.bell_pending);
+    }
+}
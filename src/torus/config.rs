@@ -0,0 +1,244 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{env, fs, io, thread};
+
+use serde::Deserialize;
+
+use super::editor::QUIT_TIMES;
+use super::error::EditorError;
+use super::render::TAB_STOP;
+
+/// How the editor signals an invalid action, like moving past the end of
+/// the buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bell {
+    /// The terminal's beep.
+    #[default]
+    Audible,
+    /// A brief flash of the screen in reverse video.
+    Visual,
+    None,
+}
+
+/// How long the screen stays reversed for a visual bell.
+const FLASH_TIME: Duration = Duration::from_millis(100);
+
+impl Bell {
+    /// The escape sequences making up the bell, to be written in order with
+    /// a short pause between them.
+    pub fn sequences(self) -> &'static [&'static [u8]] {
+        match self {
+            // \x07 - BEL
+            Bell::Audible => &[b"\x07"],
+            // \x1B[?5h - Reverse video screen, \x1B[?5l - Normal screen
+            Bell::Visual => &[b"\x1B[?5h", b"\x1B[?5l"],
+            Bell::None => &[],
+        }
+    }
+
+    /// Rings the bell through `write`.
+    pub fn ring<F>(self, mut write: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        for (i, sequence) in self.sequences().iter().enumerate() {
+            if i > 0 {
+                thread::sleep(FLASH_TIME);
+            }
+            write(sequence)?;
+        }
+        Ok(())
+    }
+}
+
+/// User settings read from `~/.config/torus/config.toml`. Missing keys keep
+/// their default values.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Columns between tab stops.
+    pub tab_width: usize,
+    /// Insert spaces up to the next tab stop instead of a tab character.
+    pub expand_tabs: bool,
+    /// Show the line number gutter on startup.
+    pub line_numbers: bool,
+    /// Number gutter rows by their distance from the cursor line on startup.
+    pub relative_line_numbers: bool,
+    /// Extra Ctrl-Q presses needed to quit with unsaved changes.
+    pub quit_times: u8,
+    /// Mark trailing spaces and tabs in a warning colour.
+    pub highlight_trailing_whitespace: bool,
+    /// Give the cursor line a different background on startup.
+    pub highlight_current_line: bool,
+    /// Remove trailing spaces and tabs from every line when saving.
+    pub strip_trailing_whitespace_on_save: bool,
+    /// Start searches ignoring case; Alt-C in the search prompt toggles it.
+    pub search_ignore_case: bool,
+    /// Bell rung on invalid actions: "audible", "visual" or "none".
+    pub bell: Bell,
+    /// Type the closing bracket or quote along with the opening one.
+    pub auto_close_brackets: bool,
+    /// Rows kept visible above and below the cursor when scrolling.
+    pub scroll_margin: usize,
+    /// Shell command Alt-M runs to build the project.
+    pub build_command: String,
+    /// Save modified files after this many milliseconds without a key
+    /// press; 0 turns auto-save off.
+    pub autosave_ms: u64,
+    /// Save modified files when the terminal window loses focus.
+    pub autosave_on_focus_lost: bool,
+    /// 1-based text column marked with a coloured background on every row,
+    /// to help keep lines short; unset draws no ruler.
+    pub ruler_column: Option<usize>,
+    /// Layout of the status bar, with tokens such as `{filename}` and
+    /// `{line}` filled in; unset keeps the built-in layout.
+    pub status_format: Option<String>,
+    /// Theme file with the highlight colours; a relative path is taken from
+    /// the config directory. Unset uses the built-in colours.
+    pub theme: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            tab_width: TAB_STOP,
+            expand_tabs: false,
+            line_numbers: false,
+            relative_line_numbers: false,
+            quit_times: QUIT_TIMES,
+            highlight_trailing_whitespace: false,
+            highlight_current_line: false,
+            strip_trailing_whitespace_on_save: false,
+            search_ignore_case: false,
+            bell: Bell::Audible,
+            auto_close_brackets: false,
+            scroll_margin: 0,
+            build_command: "make".to_string(),
+            autosave_ms: 0,
+            autosave_on_focus_lost: false,
+            ruler_column: None,
+            status_format: None,
+            theme: None,
+        }
+    }
+}
+
+impl Config {
+    /// Parses a config file's contents. A tab width of 0 is rejected since
+    /// tab expansion divides by it.
+    pub fn parse(text: &str) -> Result<Config, EditorError> {
+        let config: Config =
+            toml::from_str(text).map_err(|err| EditorError::Parse(err.message().to_string()))?;
+        if config.tab_width == 0 {
+            return Err(EditorError::Parse("tab_width must be at least 1".to_string()));
+        }
+        Ok(config)
+    }
+
+    /// Loads the user's config file. A missing file yields the defaults; an
+    /// unreadable or malformed one is an error describing the problem.
+    pub fn load() -> Result<Config, EditorError> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(err.into()),
+        };
+        Config::parse(&text)
+            .map_err(|err| EditorError::Parse(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Location of the theme file, if one is set.
+    pub fn theme_path(&self) -> Option<PathBuf> {
+        let theme = self.theme.as_ref()?;
+        match config_dir() {
+            Some(dir) if theme.is_relative() => Some(dir.join(theme)),
+            _ => Some(theme.clone()),
+        }
+    }
+}
+
+/// Directory holding the config file, or `None` if `$HOME` is not set.
+fn config_dir() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config/torus"))
+}
+
+/// Location of the config file, or `None` if `$HOME` is not set.
+fn config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_config() {
+        let text = "tab_width = 4\nexpand_tabs = true\nline_numbers = true\nrelative_line_numbers = true\nquit_times = 1\n\
+                    highlight_trailing_whitespace = true\nhighlight_current_line = true\nstrip_trailing_whitespace_on_save = true\n\
+                    search_ignore_case = true\nbell = \"visual\"\nauto_close_brackets = true\n\
+                    scroll_margin = 3\nbuild_command = \"cargo build\"\nautosave_ms = 2000\nautosave_on_focus_lost = true\ntheme = \"dark.toml\"\nstatus_format = \"{filename}\"\nruler_column = 80\n";
+
+        let config = Config::parse(text).unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                tab_width: 4,
+                expand_tabs: true,
+                line_numbers: true,
+                relative_line_numbers: true,
+                quit_times: 1,
+                highlight_trailing_whitespace: true,
+                highlight_current_line: true,
+                strip_trailing_whitespace_on_save: true,
+                search_ignore_case: true,
+                bell: Bell::Visual,
+                auto_close_brackets: true,
+                scroll_margin: 3,
+                build_command: "cargo build".to_string(),
+                autosave_ms: 2000,
+                autosave_on_focus_lost: true,
+                ruler_column: Some(80),
+                status_format: Some("{filename}".to_string()),
+                theme: Some(PathBuf::from("dark.toml")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let config = Config::parse("line_numbers = true").unwrap();
+
+        assert_eq!(config, Config { line_numbers: true, ..Config::default() });
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_malformed_config_is_an_error() {
+        assert!(Config::parse("tab_width = \"wide\"").is_err());
+        assert!(Config::parse("tab_width = 0").is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+        assert!(Config::parse("bell = \"loud\"").is_err());
+    }
+
+    #[test]
+    fn test_visual_bell_reverses_then_restores_screen() {
+        let mut written = Vec::new();
+
+        Bell::Visual.ring(|bytes| {
+            written.push(bytes.to_vec());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(written, vec![b"\x1B[?5h".to_vec(), b"\x1B[?5l".to_vec()]);
+        assert_eq!(Bell::Audible.sequences(), [b"\x07"]);
+        assert!(Bell::None.sequences().is_empty());
+    }
+}
@@ -0,0 +1,2624 @@
+use std::fs;
+use std::io::{self, Read};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::buffer::{Buffer, LineEnding};
+use super::config::Config;
+use super::error::EditorError;
+use super::highlight::{select_syntax, Highlight};
+use super::input_handler::Key;
+use super::mode::{Mode, Operator};
+use super::render::{
+    caret_notation, cx_to_render_idx, cx_to_rx, draw_row, render_row, render_row_with, rx_to_cx, str_width, truncate_to_width, AppendBuffer,
+};
+use super::terminal_handler::{get_window_size, take_window_resized, write_stdout};
+use super::theme::{ColorDepth, Palette, Theme};
+use super::positions::positions_path;
+use super::scrollbar::{scroll_thumb, SCROLLBAR_THUMB, SCROLLBAR_TRACK};
+use super::search::{match_ranges, SearchPattern};
+use super::undo::{EditOp, UndoStack};
+use super::whitespace::trailing_whitespace_start;
+
+/// Screen rows reserved below the text area for the status and message bars.
+const RESERVED_ROWS: usize = 2;
+
+/// How long a status message stays visible in the message bar.
+const STATUS_MSG_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of extra Ctrl-Q presses needed to quit while there are
+/// unsaved changes.
+pub const QUIT_TIMES: u8 = 3;
+
+/// Extra Ctrl-R presses needed to reload a buffer with unsaved changes.
+pub const RELOAD_TIMES: u8 = 1;
+
+/// Extra Ctrl-S presses needed to overwrite a file changed on disk.
+pub const OVERWRITE_TIMES: u8 = 1;
+
+/// Extra Alt-Q presses needed to close a buffer with unsaved changes.
+pub const CLOSE_TIMES: u8 = 1;
+
+/// Returns the byte offset of the char at index `cx` in `row`, or the row's
+/// length if `cx` is at or past its end.
+pub fn byte_index(row: &str, cx: usize) -> usize {
+    row.char_indices().nth(cx).map_or(row.len(), |(idx, _)| idx)
+}
+
+/// Number of chars in `row`; cursor columns are char indices.
+pub fn char_count(row: &str) -> usize {
+    row.chars().count()
+}
+
+/// A horizontal split into two stacked panes. The focused pane shows the
+/// active buffer; the other pane shows `other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Split {
+    /// Index into `buffers` of the buffer shown in the unfocused pane.
+    pub other: usize,
+    /// Whether the focused pane is the lower one.
+    pub focus_below: bool,
+}
+
+/// Text heights of the upper and lower panes of a split screen with
+/// `screen_rows` text rows. The upper pane's status bar takes one of them,
+/// and the upper pane gets the odd row out.
+pub fn pane_heights(screen_rows: usize) -> (usize, usize) {
+    let rows = screen_rows.saturating_sub(1);
+    let upper = rows.div_ceil(2);
+    (upper, rows - upper)
+}
+
+/// Gutter label of row `file_row` with the cursor on row `cursor_y`: the
+/// 1-based line number, or with `relative` set the distance from the cursor
+/// line, which shows 0 unless `absolute` is also set (the hybrid mode).
+pub fn line_number_label(file_row: usize, cursor_y: usize, absolute: bool, relative: bool) -> String {
+    if relative && (file_row != cursor_y || !absolute) {
+        file_row.abs_diff(cursor_y).to_string()
+    } else {
+        (file_row + 1).to_string()
+    }
+}
+
+/// Modification time of the file at `path`, if it can be read.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Central editor state shared by the input handling and rendering code.
+/// Per-file state lives in `buffers`; the state derefs to the active one,
+/// so `state.rows` or `state.cursor_x` always mean the buffer being edited.
+pub struct EditorState {
+    pub buffers: Vec<Buffer>,
+    /// Index into `buffers` of the buffer being edited.
+    pub active: usize,
+    /// Height of the text area, excluding the rows reserved for the bars.
+    pub screen_rows: usize,
+    pub screen_cols: usize,
+    /// Layout of the second pane, if the screen is split.
+    pub split: Option<Split>,
+    /// Whether a gutter with line numbers is drawn left of the text.
+    pub show_line_numbers: bool,
+    /// Whether the gutter counts rows from the cursor line instead, keeping
+    /// the absolute number on the cursor line if `show_line_numbers` is
+    /// also set.
+    pub relative_line_numbers: bool,
+    /// Whether spaces, tabs and line ends are drawn as visible glyphs.
+    pub show_whitespace: bool,
+    /// Whether lines wider than the screen continue on the rows below
+    /// instead of scrolling sideways.
+    pub wrap_lines: bool,
+    /// Whether the cursor line of the focused pane gets a background of
+    /// its own.
+    pub highlight_current_line: bool,
+    /// Text of the last cut or copied line.
+    pub clipboard: String,
+    pub status_msg: String,
+    pub status_msg_time: Instant,
+    /// Remark shown after the text of an open prompt, such as the search mode.
+    pub prompt_note: String,
+    /// Whether search queries are regular expressions rather than plain text.
+    pub search_regex: bool,
+    /// Whether searches ignore the case of letters.
+    pub search_ignore_case: bool,
+    /// Query of the search in progress, whose matches are highlighted.
+    pub search_pattern: Option<SearchPattern>,
+    /// Whether typed letters are commands or text.
+    pub mode: Mode,
+    /// Whether typing replaces the character under the cursor rather than
+    /// inserting before it; toggled with the Insert key.
+    pub overwrite: bool,
+    /// Normal-mode operator waiting for its second key, and when it was typed.
+    pub pending_operator: Option<(Operator, Instant)>,
+    /// Repeat count typed before a Normal-mode command, if any.
+    pub pending_count: Option<usize>,
+    /// Keys typed since macro recording started, or `None` when not recording.
+    pub recording: Option<Vec<Key>>,
+    /// Keys of the last recorded macro, replayed by `@`.
+    pub last_macro: Vec<Key>,
+    /// Set while a macro is being replayed, so it can't replay itself.
+    pub replaying: bool,
+    /// Remaining quit presses before unsaved changes are discarded.
+    pub quit_times: u8,
+    /// Remaining reload presses before unsaved changes are discarded.
+    pub reload_times: u8,
+    /// Remaining save presses before a file changed on disk is overwritten.
+    pub overwrite_times: u8,
+    /// Remaining close presses before a buffer's unsaved changes are
+    /// discarded.
+    pub close_times: u8,
+    pub quit: bool,
+    /// Settings loaded from the config file.
+    pub config: Config,
+    /// File where cursor positions are remembered between sessions, or
+    /// `None` to not remember them.
+    pub positions_path: Option<PathBuf>,
+    /// Set by `ring_bell`; the bell rings after the next redraw.
+    pub bell_pending: bool,
+    /// Escape sequences of the highlight colours, from the theme.
+    pub palette: Palette,
+    /// Buffer to go back to when leaving the build output.
+    pub build_return: Option<usize>,
+    /// When a key was last pressed, or an auto-save last tried.
+    pub last_activity: Instant,
+    /// When a key was last pressed, if swap files haven't been written
+    /// since.
+    pub swap_pending_since: Option<Instant>,
+}
+
+impl Deref for EditorState {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+}
+
+impl DerefMut for EditorState {
+    fn deref_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
+}
+
+impl EditorState {
+    /// Creates an editor state sized to the current terminal window, with
+    /// settings from the user's config file. A config error is reported in
+    /// the message bar and the defaults are used instead.
+    pub fn new() -> Result<Self, EditorError> {
+        let (rows, cols) = get_window_size()?;
+        let mut state = Self::with_size((rows as usize).saturating_sub(RESERVED_ROWS), cols as usize);
+        state.positions_path = positions_path();
+
+        match Config::load() {
+            Ok(config) => state.apply_config(config),
+            Err(err) => state.set_status_message(format!("Config error: {}", err)),
+        }
+        match state.config.theme_path().map(|path| Theme::load(&path)).transpose() {
+            Ok(theme) => state.palette = Palette::new(&theme.unwrap_or_default(), ColorDepth::detect()),
+            Err(err) => state.set_status_message(format!("Theme error: {}", err)),
+        }
+        Ok(state)
+    }
+
+    /// Switches to `config`, applying the settings that take effect at startup.
+    pub fn apply_config(&mut self, config: Config) {
+        self.show_line_numbers = config.line_numbers;
+        self.relative_line_numbers = config.relative_line_numbers;
+        self.highlight_current_line = config.highlight_current_line;
+        self.quit_times = config.quit_times;
+        self.search_ignore_case = config.search_ignore_case;
+        self.config = config;
+        for buffer in &mut self.buffers {
+            buffer.invalidate_highlight(0);
+        }
+    }
+
+    /// Creates an editor state with a text area of a fixed size.
+    pub fn with_size(screen_rows: usize, screen_cols: usize) -> Self {
+        EditorState {
+            buffers: vec![Buffer::default()],
+            active: 0,
+            screen_rows,
+            screen_cols,
+            split: None,
+            show_line_numbers: false,
+            relative_line_numbers: false,
+            show_whitespace: false,
+            wrap_lines: false,
+            highlight_current_line: false,
+            clipboard: String::new(),
+            status_msg: String::new(),
+            status_msg_time: Instant::now(),
+            prompt_note: String::new(),
+            search_regex: false,
+            search_ignore_case: false,
+            search_pattern: None,
+            mode: Mode::Normal,
+            overwrite: false,
+            pending_operator: None,
+            pending_count: None,
+            recording: None,
+            last_macro: Vec::new(),
+            replaying: false,
+            quit_times: QUIT_TIMES,
+            reload_times: RELOAD_TIMES,
+            overwrite_times: OVERWRITE_TIMES,
+            close_times: CLOSE_TIMES,
+            quit: false,
+            config: Config::default(),
+            positions_path: None,
+            bell_pending: false,
+            palette: Palette::default(),
+            build_return: None,
+            last_activity: Instant::now(),
+            swap_pending_since: None,
+        }
+    }
+
+    /// Re-queries the window size via `window_size` if the terminal has been
+    /// resized since the last check. Returns whether the size was updated.
+    pub fn check_resize<F>(&mut self, window_size: F) -> bool
+    where
+        F: FnOnce() -> Result<(u16, u16), EditorError>,
+    {
+        if !take_window_resized() {
+            return false;
+        }
+
+        match window_size() {
+            Ok((rows, cols)) => {
+                self.screen_rows = (rows as usize).saturating_sub(RESERVED_ROWS);
+                self.screen_cols = cols as usize;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Loads the file at `path` into the buffer, one row per line.
+    pub fn open(&mut self, path: &Path) -> Result<(), EditorError> {
+        let text = fs::read_to_string(path)?;
+
+        // `lines` strips the `\n`/`\r\n` terminators, yields nothing for an
+        // empty file and still returns a final line with no trailing newline.
+        self.rows = text.lines().map(String::from).collect();
+        self.line_ending = LineEnding::detect(&text);
+        self.no_final_newline = !text.is_empty() && !text.ends_with('\n');
+        self.mtime = modified_time(path);
+        self.set_filename(path.to_path_buf());
+        self.undo_stack = UndoStack::default();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Re-reads the active buffer's file from disk, keeping the cursor where
+    /// it was as far as the new contents allow. Unsaved changes need the
+    /// reload to be confirmed with further presses, like quitting.
+    pub fn reload(&mut self) {
+        let Some(path) = self.filename.clone() else {
+            self.set_status_message("No file to reload".to_string());
+            return;
+        };
+        if self.dirty && self.reload_times > 0 {
+            self.set_status_message(format!(
+                "WARNING: File has unsaved changes. Press Ctrl-R {} more times to reload.",
+                self.reload_times
+            ));
+            self.reload_times -= 1;
+            return;
+        }
+
+        match self.open(&path) {
+            Ok(()) => {
+                self.cursor_y = self.cursor_y.min(self.num_rows().saturating_sub(1));
+                self.cursor_x = self.cursor_x.min(self.row_len(self.cursor_y));
+                self.row_offset = self.row_offset.min(self.cursor_y);
+                self.selection_anchor = None;
+                self.set_status_message(format!("Reloaded: {} lines", self.num_rows()));
+            }
+            Err(err) => self.set_status_message(format!("Can't reload: {}", err)),
+        }
+    }
+
+    /// Writes the buffer back to the file it was opened from, first
+    /// stripping trailing whitespace if configured to, and terminating
+    /// every row with the buffer's line ending, except the last if the file
+    /// was loaded without one. Returns the number of bytes written.
+    pub fn save(&mut self) -> Result<usize, EditorError> {
+        if self.config.strip_trailing_whitespace_on_save && self.filename.is_some() {
+            self.strip_trailing_whitespace();
+        }
+        self.write_file()
+    }
+
+    /// Writes the rows to the buffer's file as they are and marks the
+    /// buffer clean. Returns the number of bytes written.
+    pub fn write_file(&mut self) -> Result<usize, EditorError> {
+        let Some(path) = self.filename.clone() else {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "no file name").into());
+        };
+
+        let ending = self.line_ending.as_str();
+        let mut contents = self.rows.join(ending);
+        if !self.rows.is_empty() && !self.no_final_newline {
+            contents.push_str(ending);
+        }
+        fs::write(&path, &contents)?;
+        self.mtime = modified_time(&path);
+        self.dirty = false;
+        self.remove_swap();
+        Ok(contents.len())
+    }
+
+    /// Whether the file was modified by someone else since the buffer last
+    /// loaded or saved it.
+    pub fn changed_on_disk(&self) -> bool {
+        match (self.mtime, self.filename.as_deref().and_then(modified_time)) {
+            (Some(ours), Some(theirs)) => ours != theirs,
+            _ => false,
+        }
+    }
+
+    /// Saves the buffer, first asking for a file name if it has none, and
+    /// reports the outcome in the message bar. A file changed on disk is
+    /// only overwritten once the save is confirmed with further presses.
+    pub fn save_with_prompt<R: Read>(&mut self, input: &mut R) {
+        if self.filename.is_none() {
+            let answer = self.prompt(input, "Save as: %s (ESC to cancel)", |_, _, _| {});
+            if !self.assign_filename(answer) {
+                self.set_status_message("Save aborted".to_string());
+                return;
+            }
+        }
+        if self.changed_on_disk() && self.overwrite_times > 0 {
+            self.set_status_message("File changed on disk — press Ctrl-S again to overwrite".to_string());
+            self.overwrite_times -= 1;
+            return;
+        }
+
+        match self.save() {
+            Ok(len) => {
+                self.remember_positions();
+                self.set_status_message(format!("{} bytes written to disk", len));
+            }
+            Err(err) => self.set_status_message(format!("Can't save! I/O error: {}", err)),
+        }
+    }
+
+    /// Asks for a line number and jumps to it. Input that isn't a number
+    /// leaves the cursor where it was.
+    pub fn goto_line_with_prompt<R: Read>(&mut self, input: &mut R) {
+        let Some(answer) = self.prompt(input, "Go to line: %s (ESC to cancel)", |_, _, _| {}) else {
+            return;
+        };
+
+        match answer.trim().parse::<usize>() {
+            Ok(line) => self.goto_line(line),
+            Err(_) => self.set_status_message(format!("Not a line number: {}", answer)),
+        }
+    }
+
+    /// Moves the cursor to the start of 1-based `line`, clamped to the
+    /// buffer, and scrolls it to the top of the screen.
+    pub fn goto_line(&mut self, line: usize) {
+        self.record_jump();
+        self.cursor_y = line.saturating_sub(1).min(self.num_rows().saturating_sub(1));
+        self.cursor_x = 0;
+        self.row_offset = self.cursor_y;
+    }
+
+    /// Records the answer to the Save-As prompt as the buffer's file name.
+    /// Returns `false` if the prompt was cancelled.
+    fn assign_filename(&mut self, answer: Option<String>) -> bool {
+        match answer {
+            Some(name) => {
+                self.set_filename(PathBuf::from(name));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Names the buffer's file and picks highlighting rules to match.
+    pub fn set_filename(&mut self, path: PathBuf) {
+        self.syntax = select_syntax(&path);
+        self.invalidate_highlight(0);
+        self.filename = Some(path);
+    }
+
+    /// Inserts `c` at the cursor and advances past it. With the cursor one
+    /// line past the end of the buffer, a new row is started first.
+    ///
+    /// In overwrite mode `c` replaces the character under the cursor
+    /// instead, whatever its width, so a tab or a wide character is
+    /// replaced whole; at the end of the line it is appended.
+    pub fn insert_char(&mut self, c: char) {
+        let y = self.cursor_y;
+        if y == self.num_rows() {
+            self.edit(EditOp::InsertRow { row: y, text: String::new() }, (y, 0));
+        }
+
+        let x = self.cursor_x.min(self.row_len(y));
+        let replaced = self.row(y).and_then(|row| row.chars().nth(x)).filter(|_| self.overwrite);
+        let mut ops = Vec::new();
+        if let Some(old) = replaced {
+            ops.push(EditOp::DeleteChar { at: (y, x), c: old });
+        }
+        ops.push(EditOp::InsertChar { at: (y, x), c });
+        self.edit_all(ops, (y, x + 1));
+    }
+
+    /// Breaks the line at the cursor, moving the text after it onto a new row
+    /// below. The new row inherits the current line's leading whitespace
+    /// and the cursor is placed after it.
+    pub fn insert_newline(&mut self) {
+        let y = self.cursor_y;
+        let Some(row) = self.row(y) else {
+            self.edit(EditOp::InsertRow { row: y, text: String::new() }, (y + 1, 0));
+            return;
+        };
+
+        let x = self.cursor_x.min(char_count(row));
+        // At column 0 the line just moves down, keeping its own indent.
+        let indent: Vec<char> = if x == 0 {
+            Vec::new()
+        } else {
+            row.chars().take_while(|&c| c == ' ' || c == '\t').collect()
+        };
+
+        let mut ops = vec![EditOp::SplitLine { at: (y, x) }];
+        ops.extend(
+            indent
+                .iter()
+                .enumerate()
+                .map(|(i, &c)| EditOp::InsertChar { at: (y + 1, i), c }),
+        );
+        self.edit_all(ops, (y + 1, indent.len()));
+    }
+
+    /// Inserts `text` at the cursor exactly as given, without auto-indent,
+    /// as a single undo unit. Line breaks (`\n`, `\r` or `\r\n`) split
+    /// the line.
+    pub fn paste_text(&mut self, text: &str) {
+        let (mut y, mut x) = (self.cursor_y, self.cursor_x.min(self.row_len(self.cursor_y)));
+        let mut ops = Vec::new();
+        if y == self.num_rows() {
+            ops.push(EditOp::InsertRow { row: y, text: String::new() });
+        }
+
+        for c in text.replace("\r\n", "\n").chars() {
+            if c == '\n' || c == '\r' {
+                ops.push(EditOp::SplitLine { at: (y, x) });
+                (y, x) = (y + 1, 0);
+            } else {
+                ops.push(EditOp::InsertChar { at: (y, x), c });
+                x += 1;
+            }
+        }
+        if !text.is_empty() {
+            self.edit_all(ops, (y, x));
+        }
+    }
+
+    /// Deletes the character before the cursor (Backspace). At the start of
+    /// a line, the line is joined onto the end of the previous one.
+    pub fn delete_char(&mut self) {
+        let y = self.cursor_y;
+        if y >= self.num_rows() || (self.cursor_x == 0 && y == 0) {
+            self.ring_bell();
+            return;
+        }
+
+        let x = self.cursor_x.min(self.row_len(y));
+        if x > 0 {
+            let row = &self.rows[y];
+            let c = row[byte_index(row, x - 1)..].chars().next().unwrap_or_default();
+            self.edit(EditOp::DeleteChar { at: (y, x - 1), c }, (y, x - 1));
+        } else {
+            let len = self.row_len(y - 1);
+            self.edit(EditOp::JoinLine { at: (y - 1, len) }, (y - 1, len));
+        }
+    }
+
+    /// Inserts a tab, or with `expand_tabs` set, spaces up to the next tab stop.
+    pub fn insert_tab(&mut self) {
+        if !self.config.expand_tabs {
+            self.insert_char('\t');
+            return;
+        }
+
+        let tab_width = self.config.tab_width;
+        let spaces = tab_width - self.cursor_rx() % tab_width;
+        for _ in 0..spaces {
+            self.insert_char(' ');
+        }
+    }
+
+    /// Deletes backwards for the Backspace key. With `expand_tabs` set and
+    /// only spaces before the cursor, deletes back to the previous tab stop.
+    pub fn backspace(&mut self) {
+        let x = self.cursor_x.min(self.row_len(self.cursor_y));
+        let in_indent = self
+            .row(self.cursor_y)
+            .is_some_and(|row| row.chars().take(x).all(|c| c == ' '));
+
+        let count = if self.config.expand_tabs && in_indent && x > 0 {
+            (x - 1) % self.config.tab_width + 1
+        } else {
+            1
+        };
+        for _ in 0..count {
+            self.delete_char();
+        }
+    }
+
+    /// Deletes the character under the cursor (Delete) by stepping over it
+    /// and deleting backwards. At the end of a line the next line is joined.
+    pub fn delete_char_forward(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(row) = self.row(self.cursor_y) else {
+            return;
+        };
+
+        if self.cursor_x < char_count(row) {
+            self.cursor_x += 1;
+        } else if self.cursor_y + 1 < self.num_rows() {
+            self.cursor_y += 1;
+            self.cursor_x = 0;
+        } else {
+            return;
+        }
+        self.delete_char();
+    }
+
+    /// Moves the cursor line into the clipboard. Cutting the only line
+    /// leaves a single empty row behind.
+    pub fn cut_line(&mut self) {
+        let y = self.cursor_y;
+        let Some(text) = self.row(y).map(str::to_string) else {
+            return;
+        };
+
+        self.clipboard = text.clone();
+        let mut ops = vec![EditOp::RemoveRow { row: y, text }];
+        if self.num_rows() == 1 {
+            ops.push(EditOp::InsertRow { row: 0, text: String::new() });
+        }
+        let remaining = (self.num_rows() - 1).max(1);
+        self.edit_all(ops, (y.min(remaining - 1), 0));
+    }
+
+    /// Copies the selection into the clipboard and ends it, or copies the
+    /// cursor line if nothing is selected.
+    pub fn copy_line(&mut self) {
+        if self.selection_anchor.is_some() {
+            self.clipboard = self.selected_text();
+            self.selection_anchor = None;
+            self.set_status_message("Copied selection".to_string());
+        } else {
+            self.yank_line();
+        }
+    }
+
+    /// Copies the cursor line into the clipboard, ignoring any selection.
+    pub fn yank_line(&mut self) {
+        if let Some(row) = self.row(self.cursor_y) {
+            self.clipboard = row.to_string();
+            self.set_status_message("Copied line".to_string());
+        }
+    }
+
+    /// Inserts the clipboard as new lines below the cursor line and moves
+    /// the cursor onto the first of them.
+    pub fn paste_line(&mut self) {
+        let row = (self.cursor_y + 1).min(self.num_rows());
+        let ops = self
+            .clipboard
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| EditOp::InsertRow { row: row + i, text: line.to_string() })
+            .collect();
+        self.edit_all(ops, (row, 0));
+    }
+
+    /// Inserts a copy of the cursor line below it and moves the cursor onto
+    /// the copy, keeping its column.
+    pub fn duplicate_line(&mut self) {
+        let y = self.cursor_y;
+        let Some(text) = self.row(y).map(str::to_string) else {
+            return;
+        };
+        let x = self.cursor_x;
+        self.edit(EditOp::InsertRow { row: y + 1, text }, (y + 1, x));
+    }
+
+    /// Swaps the cursor line with the one above, taking the cursor along.
+    pub fn move_line_up(&mut self) {
+        match self.cursor_y.checked_sub(1) {
+            Some(target) if self.cursor_y < self.num_rows() => self.move_line_to(target),
+            _ => self.ring_bell(),
+        }
+    }
+
+    /// Swaps the cursor line with the one below, taking the cursor along.
+    pub fn move_line_down(&mut self) {
+        let target = self.cursor_y + 1;
+        if target < self.num_rows() {
+            self.move_line_to(target);
+        } else {
+            self.ring_bell();
+        }
+    }
+
+    /// Moves the cursor line to row `target` as one undo unit.
+    fn move_line_to(&mut self, target: usize) {
+        let y = self.cursor_y;
+        let text = self.rows[y].clone();
+        let ops = vec![EditOp::RemoveRow { row: y, text: text.clone() }, EditOp::InsertRow { row: target, text }];
+        let x = self.cursor_x;
+        self.edit_all(ops, (target, x));
+    }
+
+    /// Quits, unless there are unsaved changes and the user has not yet
+    /// pressed quit enough consecutive times to confirm discarding them.
+    pub fn request_quit(&mut self) {
+        if self.any_dirty() && self.quit_times > 0 {
+            self.set_status_message(format!(
+                "WARNING: Files have unsaved changes. Press Ctrl-Q {} more times to quit.",
+                self.quit_times
+            ));
+            self.quit_times -= 1;
+        } else {
+            self.quit = true;
+        }
+    }
+
+    /// True if any open buffer has unsaved changes.
+    pub fn any_dirty(&self) -> bool {
+        self.buffers.iter().any(|buffer| buffer.dirty)
+    }
+
+    /// Adds an empty buffer and makes it the active one.
+    pub fn add_buffer(&mut self) {
+        self.buffers.push(Buffer::default());
+        self.active = self.buffers.len() - 1;
+    }
+
+    /// Switches to the next buffer, wrapping around after the last.
+    pub fn next_buffer(&mut self) {
+        self.active = (self.active + 1) % self.buffers.len();
+    }
+
+    /// Switches to the previous buffer, wrapping around before the first.
+    pub fn prev_buffer(&mut self) {
+        self.active = (self.active + self.buffers.len() - 1) % self.buffers.len();
+    }
+
+    /// Closes the active buffer, unless it has unsaved changes and close
+    /// has not yet been pressed enough consecutive times to confirm
+    /// discarding them. The next buffer gets the focus; closing the only
+    /// buffer quits.
+    pub fn close_buffer(&mut self) {
+        if self.dirty && self.close_times > 0 {
+            self.set_status_message(format!(
+                "WARNING: File has unsaved changes. Press Alt-Q {} more times to close it.",
+                self.close_times
+            ));
+            self.close_times -= 1;
+            return;
+        }
+        self.remove_active_buffer();
+    }
+
+    /// Drops the active buffer and its swap file, keeping the other indices
+    /// into `buffers` pointing at the same buffers. A split pane showing it
+    /// closes too.
+    pub fn remove_active_buffer(&mut self) {
+        if self.buffers.len() == 1 {
+            self.quit = true;
+            return;
+        }
+        let closed = self.active;
+        self.remove_swap();
+        self.buffers.remove(closed);
+        let shifted = |index: usize| if index > closed { index - 1 } else { index };
+
+        // The next buffer has moved down into the closed one's index.
+        if self.active == self.buffers.len() {
+            self.active = 0;
+        }
+        self.split = self
+            .split
+            .filter(|split| split.other != closed)
+            .map(|split| Split { other: shifted(split.other), ..split });
+        self.build_return = self.build_return.filter(|&index| index != closed).map(shifted);
+    }
+
+    /// Opens `path` in a buffer of its own, reusing the active buffer if it
+    /// is an untouched scratch buffer, at the cursor position it was last
+    /// left at. A missing file starts a new, empty one with that name; other
+    /// errors are reported in the message bar. Either way a swap file left
+    /// for it is reported.
+    pub fn open_in_new_buffer(&mut self, path: &Path) {
+        let previous = self.active;
+        if !self.is_scratch() {
+            self.add_buffer();
+        }
+
+        match self.open(path) {
+            Ok(()) => {
+                self.restore_position();
+                self.check_swap();
+            }
+            Err(err) if err.is_io_kind(io::ErrorKind::NotFound) => {
+                self.set_filename(path.to_path_buf());
+                self.check_swap();
+            }
+            Err(err) => {
+                if self.active != previous {
+                    self.buffers.pop();
+                    self.active = previous;
+                }
+                self.set_status_message(format!("Can't open {}: {}", path.display(), err));
+            }
+        }
+    }
+
+    /// Asks for a file name and opens it in a new buffer.
+    pub fn open_with_prompt<R: Read>(&mut self, input: &mut R) {
+        if let Some(name) = self.prompt(input, "Open: %s (ESC to cancel)", |_, _, _| {}) {
+            self.open_in_new_buffer(Path::new(&name));
+        }
+    }
+
+    /// Switches the active buffer between LF and CRLF line endings, taking
+    /// effect when it is next saved.
+    pub fn toggle_line_ending(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        self.line_ending = match self.line_ending {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        };
+        self.dirty = true;
+        let name = match self.line_ending {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        };
+        self.set_status_message(format!("Line endings: {}", name));
+    }
+
+    /// Makes the active buffer read-only, or editable again.
+    pub fn toggle_read_only(&mut self) {
+        self.read_only = !self.read_only;
+        let msg = if self.read_only { "Read-only" } else { "Editable" };
+        self.set_status_message(msg.to_string());
+    }
+
+    /// Splits the screen into two panes, the new one showing the next
+    /// buffer, or goes back to a single pane showing the active buffer.
+    pub fn toggle_split(&mut self) {
+        self.split = match self.split {
+            Some(_) => None,
+            None => Some(Split {
+                other: (self.active + 1) % self.buffers.len(),
+                focus_below: false,
+            }),
+        };
+    }
+
+    /// Moves the focus to the other pane of a split screen, so its buffer
+    /// becomes the active one.
+    pub fn switch_pane(&mut self) {
+        if let Some(split) = &mut self.split {
+            std::mem::swap(&mut split.other, &mut self.active);
+            split.focus_below = !split.focus_below;
+        }
+    }
+
+    /// Text height of the focused pane.
+    pub fn text_rows(&self) -> usize {
+        match self.split {
+            Some(split) => {
+                let (upper, lower) = pane_heights(self.body_rows());
+                if split.focus_below { lower } else { upper }
+            }
+            None => self.body_rows(),
+        }
+    }
+
+    /// Rows of the text area left for the panes below the tab bar.
+    fn body_rows(&self) -> usize {
+        self.screen_rows.saturating_sub(self.tab_bar_rows())
+    }
+
+    /// Screen row at which the focused pane starts.
+    fn pane_top(&self) -> usize {
+        match self.split {
+            Some(split) if split.focus_below => self.tab_bar_rows() + pane_heights(self.body_rows()).0 + 1,
+            _ => self.tab_bar_rows(),
+        }
+    }
+
+    /// Signals an invalid action with the configured bell, once the screen
+    /// has been redrawn.
+    pub fn ring_bell(&mut self) {
+        self.bell_pending = true;
+    }
+
+    /// Shows `msg` in the message bar for the next few seconds.
+    pub fn set_status_message(&mut self, msg: String) {
+        self.status_msg = msg;
+        self.status_msg_time = Instant::now();
+    }
+
+    /// Re-highlights the stale rows of the active buffer.
+    pub fn update_highlights(&mut self) {
+        let tab_stop = self.config.tab_width;
+        self.buffers[self.active].update_highlights(tab_stop);
+        if let Some(split) = self.split {
+            self.buffers[split.other].update_highlights(tab_stop);
+        }
+    }
+
+    /// Number of lines in the text buffer.
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns the line at `idx`, if the buffer has one.
+    pub fn row(&self, idx: usize) -> Option<&str> {
+        self.rows.get(idx).map(String::as_str)
+    }
+
+    /// Length in chars of row `y`, or 0 past the end of the buffer.
+    pub fn row_len(&self, y: usize) -> usize {
+        self.row(y).map_or(0, char_count)
+    }
+
+    /// Moves the cursor one cell for an arrow key. The cursor may rest one
+    /// past the end of a line and one line past the end of the buffer.
+    pub fn move_cursor(&mut self, key: Key) {
+        let row_len = self.row(self.cursor_y).map_or(0, char_count);
+
+        match key {
+            Key::ArrowLeft if self.cursor_x > 0 => self.cursor_x -= 1,
+            Key::ArrowRight if self.cursor_x < row_len => self.cursor_x += 1,
+            Key::ArrowUp if self.cursor_y > 0 => self.cursor_y -= 1,
+            Key::ArrowDown if self.cursor_y < self.num_rows() => self.cursor_y += 1,
+            _ => self.ring_bell(),
+        }
+
+        // Snap to the end of the new line if it is shorter than the old one.
+        let row_len = self.row(self.cursor_y).map_or(0, char_count);
+        self.cursor_x = self.cursor_x.min(row_len);
+    }
+
+    /// Moves the cursor a screenful up or down, stopping at the ends of the
+    /// buffer.
+    pub fn move_page(&mut self, key: Key) {
+        self.record_jump();
+        let page = self.text_rows();
+        self.cursor_y = match key {
+            Key::PageUp => self.cursor_y.saturating_sub(page),
+            _ => (self.cursor_y + page).min(self.num_rows()),
+        };
+        self.cursor_x = self.cursor_x.min(self.row_len(self.cursor_y));
+    }
+
+    /// Moves the cursor to the text under the 1-based terminal cell `(x, y)`,
+    /// as reported by a mouse click, focusing the pane clicked in. A click
+    /// on a tab switches to its buffer. Clicks outside the text area are
+    /// ignored; clicks past the end of a line or the buffer land on its
+    /// last position.
+    pub fn click(&mut self, x: u16, y: u16) {
+        let (col, mut screen_row) = ((x as usize).saturating_sub(1), (y as usize).saturating_sub(1));
+        if screen_row < self.tab_bar_rows() {
+            if let Some(index) = self.tab_at(col) {
+                self.active = index;
+            }
+            return;
+        }
+        if let Some(split) = self.split {
+            let lower_top = self.tab_bar_rows() + pane_heights(self.body_rows()).0 + 1;
+            if split.focus_below != (screen_row >= lower_top) {
+                self.switch_pane();
+            }
+        }
+        screen_row = match screen_row.checked_sub(self.pane_top()) {
+            Some(row) => row,
+            None => return,
+        };
+        if screen_row >= self.text_rows() || self.num_rows() == 0 {
+            return;
+        }
+
+        let lines = self.display_lines(self, self.text_rows(), self.text_cols());
+        let (file_row, start) = match lines.get(screen_row).or(lines.last()) {
+            Some(line) => (line.file_row, line.start),
+            None => (self.num_rows() - 1, self.col_offset),
+        };
+        self.cursor_y = file_row;
+        let rx = col.saturating_sub(self.gutter_width()) + start;
+        self.cursor_x = self.row(self.cursor_y).map_or(0, |row| rx_to_cx(row, rx, self.config.tab_width));
+    }
+
+    /// Scrolls the view by `delta` lines (negative is up) without moving the
+    /// cursor. The view stops at the top of the buffer, at most a screenful
+    /// past its last line, and wherever the cursor would leave the screen.
+    pub fn scroll_view(&mut self, delta: isize) {
+        let min = (self.cursor_y + 1).saturating_sub(self.text_rows());
+        let max = self.cursor_y.min(self.num_rows().saturating_sub(1));
+        self.row_offset = self.row_offset.saturating_add_signed(delta).clamp(min, max.max(min));
+    }
+
+    /// Scrolls so the cursor line is in the middle of the screen.
+    pub fn scroll_cursor_to_center(&mut self) {
+        self.row_offset = self.cursor_y.saturating_sub(self.text_rows() / 2);
+    }
+
+    /// Scrolls so the cursor line is at the top of the screen.
+    pub fn scroll_cursor_to_top(&mut self) {
+        self.row_offset = self.cursor_y;
+    }
+
+    /// Scrolls so the cursor line is at the bottom of the screen.
+    pub fn scroll_cursor_to_bottom(&mut self) {
+        self.row_offset = (self.cursor_y + 1).saturating_sub(self.text_rows());
+    }
+
+    /// Rendered column of the cursor, accounting for expanded tabs and
+    /// double-width characters.
+    pub fn cursor_rx(&self) -> usize {
+        self.row(self.cursor_y)
+            .map_or(self.cursor_x, |row| cx_to_rx(row, self.cursor_x, self.config.tab_width))
+    }
+
+    /// Width of the line number gutter: enough digits for the last line
+    /// number plus a separating space, or 0 when line numbers are hidden.
+    pub fn gutter_width(&self) -> usize {
+        self.gutter_width_of(self)
+    }
+
+    /// Width of the gutter drawn beside `buffer`.
+    fn gutter_width_of(&self, buffer: &Buffer) -> usize {
+        if !self.show_line_numbers && !self.relative_line_numbers {
+            return 0;
+        }
+        buffer.rows.len().max(1).to_string().len() + 1
+    }
+
+    /// Width of the text area, excluding the gutter and the scrollbar.
+    pub fn text_cols(&self) -> usize {
+        let scrollbar = scroll_thumb(self.row_offset, self.text_rows(), self.num_rows()).is_some();
+        self.screen_cols.saturating_sub(self.gutter_width() + usize::from(scrollbar))
+    }
+
+    /// Adjusts `row_offset` and `col_offset` so the cursor is inside the
+    /// visible window, with `scroll_margin` rows of context above and below
+    /// it where the file has them.
+    pub fn scroll(&mut self) {
+        if self.wrap_lines {
+            self.scroll_wrapped();
+            return;
+        }
+        let rx = self.cursor_rx();
+        let text_cols = self.text_cols();
+        let text_rows = self.text_rows();
+        // A margin over half the window would leave no row the cursor can rest on.
+        let margin = self.config.scroll_margin.min(text_rows.saturating_sub(1) / 2);
+        let margin_below = margin.min(self.num_rows().saturating_sub(self.cursor_y + 1));
+
+        if self.cursor_y < self.row_offset + margin {
+            self.row_offset = self.cursor_y.saturating_sub(margin);
+        }
+        if self.cursor_y + margin_below >= self.row_offset + text_rows {
+            self.row_offset = self.cursor_y + margin_below + 1 - text_rows;
+        }
+        if rx < self.col_offset {
+            self.col_offset = rx;
+        }
+        if rx >= self.col_offset + text_cols {
+            self.col_offset = rx + 1 - text_cols.max(1);
+        }
+    }
+
+    /// Redraws the screen and places the terminal cursor at the logical cursor.
+    pub fn refresh_screen(&mut self) -> Result<(), EditorError> {
+        self.update_highlights();
+
+        let mut buf = AppendBuffer::new();
+        self.draw(&mut buf);
+        buf.flush()?;
+
+        if std::mem::take(&mut self.bell_pending) {
+            self.config.bell.ring(write_stdout)?;
+        }
+        Ok(())
+    }
+
+    /// Builds a complete frame into `buf`.
+    fn draw(&self, buf: &mut AppendBuffer) {
+        // \x1B[?25l - Hide the cursor so it doesn't jump around mid-paint
+        buf.append("\x1B[?25l");
+        // \x1B[H - Home the cursor before painting the rows
+        buf.append("\x1B[H");
+        self.draw_tab_bar(buf);
+        match self.split {
+            Some(split) => {
+                let (upper, lower) = pane_heights(self.body_rows());
+                let (top, bottom) = if split.focus_below {
+                    (split.other, self.active)
+                } else {
+                    (self.active, split.other)
+                };
+                self.draw_pane(buf, top, upper);
+                self.draw_pane(buf, bottom, lower);
+            }
+            None => self.draw_pane(buf, self.active, self.body_rows()),
+        }
+        self.draw_message_bar(buf);
+        buf.append(&self.cursor_position_sequence());
+        // \x1B[?25h - Show the cursor again once the frame is complete
+        buf.append("\x1B[?25h");
+    }
+
+    /// Draws a pane `height` rows tall showing buffer `index`, followed by
+    /// its status bar.
+    fn draw_pane(&self, buf: &mut AppendBuffer, index: usize, height: usize) {
+        self.draw_buffer_rows(buf, index, height);
+        self.draw_buffer_status(buf, index);
+    }
+
+    /// Draws `height` visible lines of buffer `index`, marking rows past the
+    /// end of the buffer with `~`. The selection is only shown in the
+    /// focused pane.
+    pub fn draw_buffer_rows(&self, buf: &mut AppendBuffer, index: usize, height: usize) {
+        let buffer = &self.buffers[index];
+        let gutter = self.gutter_width_of(buffer);
+        let thumb = scroll_thumb(buffer.row_offset, height, buffer.rows.len());
+        let text_cols = self.screen_cols.saturating_sub(gutter + usize::from(thumb.is_some()));
+        let brackets = self.matched_brackets(index);
+        let lines = self.display_lines(buffer, height, text_cols);
+
+        for y in 0..height {
+            let line = lines.get(y);
+            let mut ruler = None;
+            let current_line = self.highlight_current_line
+                && index == self.active
+                && line.is_some_and(|line| line.file_row == buffer.cursor_y);
+            if gutter > 0 {
+                // Rows past the end of the buffer and continuations of
+                // wrapped lines get a blank gutter.
+                let number = match line {
+                    Some(line) if line.first => line_number_label(
+                        line.file_row,
+                        buffer.cursor_y,
+                        self.show_line_numbers,
+                        self.relative_line_numbers,
+                    ),
+                    _ => String::new(),
+                };
+                buf.append(&format!("{number:>width$} ", width = gutter - 1));
+            }
+
+            match line {
+                Some(line) => {
+                    let row = &buffer.rows[line.file_row];
+                    let rendered = render_row_with(row, self.config.tab_width, self.show_whitespace);
+                    let hl = self.drawn_highlight(index, line.file_row, &brackets);
+                    let background = if current_line { self.palette.sgr(Highlight::CurrentLine) } else { "" };
+                    draw_row(buf, &rendered, &hl, line.start, text_cols, &self.palette, background);
+                    ruler = self.ruler_cell(&rendered, &hl, gutter, line.start, text_cols);
+                }
+                None if buffer.rows.is_empty() && y == height / 3 => {
+                    self.draw_welcome(buf, text_cols);
+                }
+                None => buf.append("~"),
+            }
+
+            // \x1B[K - Clear the rest of the line, in the cursor line's
+            // background if it has one
+            buf.append("\x1B[K");
+            if current_line {
+                // \x1B[m - Back to normal attributes
+                buf.append("\x1B[m");
+            }
+            if let Some(cell) = ruler {
+                self.draw_ruler(buf, cell);
+            }
+            if let Some(thumb) = &thumb {
+                // \x1B[{n}G - Move to column n of the line
+                buf.append(&format!("\x1B[{}G", self.screen_cols));
+                buf.append(if thumb.contains(&y) { SCROLLBAR_THUMB } else { SCROLLBAR_TRACK });
+            }
+            buf.append("\r\n");
+        }
+    }
+
+    /// The bracket under the cursor and its match, if buffer `index` is the
+    /// active one and the cursor is on a matched bracket.
+    fn matched_brackets(&self, index: usize) -> Vec<(usize, usize)> {
+        match self.match_bracket() {
+            Some(other) if index == self.active => vec![(self.cursor_y, self.cursor_x), other],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Highlight of row `file_row` of buffer `index` as drawn: the cached
+    /// syntax highlight with control characters, trailing whitespace, the
+    /// focused pane's search matches and selection, and `brackets` laid
+    /// over it.
+    fn drawn_highlight(&self, index: usize, file_row: usize, brackets: &[(usize, usize)]) -> Vec<Highlight> {
+        let buffer = &self.buffers[index];
+        let tab_stop = self.config.tab_width;
+        let Some(row) = buffer.rows.get(file_row) else {
+            return Vec::new();
+        };
+
+        let mut hl = buffer.highlights.get(file_row).map_or(Vec::new(), |h| h.hl.clone());
+        for (cx, c) in row.chars().enumerate() {
+            if caret_notation(c).is_some() {
+                let idx = cx_to_render_idx(row, cx, tab_stop);
+                hl.resize(hl.len().max(idx + 2), Highlight::Normal);
+                hl[idx..idx + 2].fill(Highlight::Control);
+            }
+        }
+        if self.config.highlight_trailing_whitespace {
+            let from = if index == self.active {
+                self.trailing_whitespace_from(file_row)
+            } else {
+                trailing_whitespace_start(row)
+            };
+            let (from, to) = (cx_to_render_idx(row, from, tab_stop), render_row(row, tab_stop).chars().count());
+            hl.resize(hl.len().max(to), Highlight::Normal);
+            hl[from.min(to)..to].fill(Highlight::TrailingWhitespace);
+        }
+        if index == self.active
+            && let Some(pattern) = &self.search_pattern
+        {
+            for (from, to) in match_ranges(row, pattern) {
+                let current = file_row == self.cursor_y && from == self.cursor_x;
+                let class = if current { Highlight::CurrentMatch } else { Highlight::SearchMatch };
+                let (from, to) = (cx_to_render_idx(row, from, tab_stop), cx_to_render_idx(row, to, tab_stop));
+                hl.resize(hl.len().max(to), Highlight::Normal);
+                hl[from..to].fill(class);
+            }
+        }
+        if index == self.active
+            && let Some((from, to)) = self.selected_cols(file_row)
+        {
+            let (from, to) = (cx_to_render_idx(row, from, tab_stop), cx_to_render_idx(row, to, tab_stop));
+            hl.resize(hl.len().max(to), Highlight::Normal);
+            hl[from.min(to)..to].fill(Highlight::Selection);
+        }
+        for &(y, x) in brackets {
+            if y == file_row {
+                let idx = cx_to_render_idx(row, x, tab_stop);
+                hl.resize(hl.len().max(idx + 1), Highlight::Normal);
+                hl[idx] = Highlight::MatchBracket;
+            }
+        }
+        hl
+    }
+
+    /// Draws the status bar below the pane showing buffer `index`, in
+    /// reverse video if it has the focus.
+    pub fn draw_buffer_status(&self, buf: &mut AppendBuffer, index: usize) {
+        if let Some(format) = &self.config.status_format {
+            let status = self.expand_status_format(format, index);
+            let status = truncate_to_width(&status, self.screen_cols);
+            if index == self.active {
+                // \x1B[7m - Reverse video
+                buf.append("\x1B[7m");
+            }
+            buf.append(status);
+            buf.append(&" ".repeat(self.screen_cols - str_width(status)));
+            // \x1B[m - Back to normal attributes
+            buf.append("\x1B[m");
+            buf.append("\r\n");
+            return;
+        }
+
+        let buffer = &self.buffers[index];
+        let name = buffer.display_name();
+        let modified = if buffer.dirty { " (modified)" } else { "" };
+        let crlf = if buffer.line_ending == LineEnding::Crlf { " [CRLF]" } else { "" };
+        let read_only = if buffer.read_only { " [RO]" } else { "" };
+        let buffer_pos = if self.buffers.len() > 1 {
+            format!(" [{}/{}]", index + 1, self.buffers.len())
+        } else {
+            String::new()
+        };
+
+        let status = format!(
+            "{} - {} lines{}{}{}{}",
+            truncate_to_width(&name, 20),
+            buffer.rows.len(),
+            modified,
+            read_only,
+            crlf,
+            buffer_pos
+        );
+        let status = truncate_to_width(&status, self.screen_cols);
+        // The mode belongs to the editor, so only the active pane shows it.
+        let mode = if index == self.active {
+            format!("{} | ", self.mode_label())
+        } else {
+            String::new()
+        };
+        let position = format!(
+            "{}{} | {}/{}",
+            mode,
+            buffer.syntax.map_or("no ft", |syntax| syntax.name),
+            buffer.cursor_y + 1,
+            buffer.rows.len()
+        );
+
+        if index == self.active {
+            // \x1B[7m - Reverse video
+            buf.append("\x1B[7m");
+        }
+        buf.append(status);
+
+        let mut len = str_width(status);
+        while len < self.screen_cols {
+            if self.screen_cols - len == position.len() {
+                buf.append(&position);
+                break;
+            }
+            buf.append(" ");
+            len += 1;
+        }
+
+        // \x1B[m - Back to normal attributes
+        buf.append("\x1B[m");
+        buf.append("\r\n");
+    }
+
+    /// The mode as the status bar shows it, e.g. `NORMAL REC`. Insert mode
+    /// shows as `REPLACE` while typing overwrites.
+    pub fn mode_label(&self) -> String {
+        let mode = if self.mode == Mode::Insert && self.overwrite { "REPLACE" } else { self.mode.label() };
+        let recording = if self.recording.is_some() { " REC" } else { "" };
+        format!("{}{}", mode, recording)
+    }
+
+    /// Draws the message bar, showing the status message until it expires.
+    fn draw_message_bar(&self, buf: &mut AppendBuffer) {
+        buf.append("\x1B[K");
+        if self.status_msg_time.elapsed() < STATUS_MSG_TIMEOUT {
+            buf.append(truncate_to_width(&self.status_msg, self.screen_cols));
+        }
+    }
+
+    /// Draws a welcome banner centred in `text_cols` columns, shown while
+    /// the buffer is empty.
+    fn draw_welcome(&self, buf: &mut AppendBuffer, text_cols: usize) {
+        let welcome = format!("Torus editor -- version {}", env!("CARGO_PKG_VERSION"));
+        let welcome = truncate_to_width(&welcome, text_cols);
+
+        let padding = (text_cols - welcome.len()) / 2;
+        if padding > 0 {
+            buf.append("~");
+            buf.append(&" ".repeat(padding - 1));
+        }
+        buf.append(welcome);
+    }
+
+    /// Builds the escape sequence that moves the terminal cursor to the
+    /// logical cursor, converting the 0-based fields to 1-based ANSI ones.
+    fn cursor_position_sequence(&self) -> String {
+        let (row, col) = if self.wrap_lines {
+            self.wrapped_cursor_position()
+        } else {
+            (self.cursor_y - self.row_offset, self.cursor_rx() - self.col_offset)
+        };
+        format!("\x1B[{};{}H", self.pane_top() + row + 1, self.gutter_width() + col + 1)
+    }
+}
+
+#[cfg(test)]
+mod editor_tests {
+    use super::*;
+    use crate::torus::render::TAB_STOP;
+    use std::fs::File;
+
+    /// Returns a path in the temp directory unique to this test process.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("torus-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_new_state_starts_at_origin() {
+        let state = EditorState::with_size(24, 80);
+
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 0));
+        assert_eq!(state.screen_rows, 24);
+        assert_eq!(state.screen_cols, 80);
+        assert_eq!(state.num_rows(), 0);
+        assert!(!state.quit);
+    }
+
+    #[test]
+    fn test_move_left_at_column_zero_stays_put() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.move_cursor(Key::ArrowLeft);
+        state.move_cursor(Key::ArrowUp);
+
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 0));
+    }
+
+    #[test]
+    fn test_move_cursor_clamps_at_bounds() {
+        let mut state = EditorState::with_size(3, 4);
+        state.rows = vec!["abc".to_string(), "d".to_string()];
+
+        for _ in 0..10 {
+            state.move_cursor(Key::ArrowRight);
+        }
+        assert_eq!((state.cursor_x, state.cursor_y), (3, 0));
+
+        for _ in 0..10 {
+            state.move_cursor(Key::ArrowDown);
+        }
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 2));
+    }
+
+    #[test]
+    fn test_bell_rings_at_buffer_boundaries() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string()];
+
+        state.move_cursor(Key::ArrowRight);
+        assert!(!state.bell_pending);
+        state.move_cursor(Key::ArrowUp);
+        assert!(state.bell_pending);
+
+        state.bell_pending = false;
+        state.cursor_x = 0;
+        state.delete_char();
+        assert!(state.bell_pending);
+    }
+
+    #[test]
+    fn test_move_cursor_snaps_to_shorter_line() {
+        let mut state = EditorState::with_size(3, 4);
+        state.rows = vec!["abc".to_string(), "d".to_string()];
+        state.cursor_x = 3;
+
+        state.move_cursor(Key::ArrowDown);
+
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 1));
+    }
+
+    #[test]
+    fn test_cursor_position_sequence_is_one_based() {
+        let mut state = EditorState::with_size(24, 80);
+        state.cursor_x = 5;
+        state.cursor_y = 3;
+
+        assert_eq!(state.cursor_position_sequence(), "\x1B[4;6H");
+    }
+
+    #[test]
+    fn test_draw_hides_cursor_during_refresh() {
+        let state = EditorState::with_size(24, 80);
+        let mut buf = AppendBuffer::new();
+
+        state.draw(&mut buf);
+
+        assert!(buf.as_bytes().starts_with(b"\x1B[?25l"));
+        assert!(buf.as_bytes().ends_with(b"\x1B[?25h"));
+    }
+
+    #[test]
+    fn test_rows_are_drawn_truncated_to_screen_width() {
+        let mut state = EditorState::with_size(3, 5);
+        state.rows = vec!["hello, world".to_string(), "hi".to_string()];
+
+        assert_eq!(state.num_rows(), 2);
+        assert_eq!(state.row(1), Some("hi"));
+        assert_eq!(state.row(2), None);
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        assert_eq!(buf.as_bytes(), b"hello\x1B[K\r\nhi\x1B[K\r\n~\x1B[K\r\n");
+    }
+
+    #[test]
+    fn test_open_loads_lines_without_terminators() {
+        let path = temp_path("open-three-lines");
+        fs::write(&path, "first\r\nsecond\nthird").unwrap();
+
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(state.rows, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_open_empty_file_has_no_rows() {
+        let path = temp_path("open-empty");
+        fs::write(&path, "").unwrap();
+
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(state.num_rows(), 0);
+    }
+
+    #[test]
+    fn test_save_writes_edits_back_to_disk() {
+        let path = temp_path("save-roundtrip");
+        fs::write(&path, "one\ntwo\n").unwrap();
+
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        state.rows[1] = "deux".to_string();
+
+        let written = state.save().unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(contents, "one\ndeux\n");
+        assert_eq!(written, contents.len());
+    }
+
+    #[test]
+    fn test_save_without_filename_is_an_error() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows.push("unsaved".to_string());
+
+        assert!(state.save().is_err());
+    }
+
+    #[test]
+    fn test_scroll_follows_cursor_down_and_up() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = (0..200).map(|n| n.to_string()).collect();
+
+        state.cursor_y = 100;
+        state.scroll();
+        assert_eq!(state.row_offset, 77);
+
+        state.cursor_y = 10;
+        state.scroll();
+        assert_eq!(state.row_offset, 10);
+    }
+
+    #[test]
+    fn test_scroll_keeps_margin_around_cursor() {
+        let mut state = EditorState::with_size(10, 80);
+        state.config.scroll_margin = 3;
+        state.rows = (0..50).map(|n| n.to_string()).collect();
+
+        // Three rows stay visible below the cursor...
+        state.cursor_y = 7;
+        state.scroll();
+        assert_eq!(state.row_offset, 1);
+
+        // ...and above it.
+        state.row_offset = 10;
+        state.cursor_y = 12;
+        state.scroll();
+        assert_eq!(state.row_offset, 9);
+
+        // Near the ends of the file there is no room for a margin.
+        state.cursor_y = 1;
+        state.scroll();
+        assert_eq!(state.row_offset, 0);
+        state.cursor_y = 49;
+        state.scroll();
+        assert_eq!(state.row_offset, 40);
+    }
+
+    #[test]
+    fn test_scroll_cursor_to_center_top_and_bottom() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["x".to_string(); 100];
+        state.cursor_y = 49;
+
+        state.scroll_cursor_to_center();
+        assert_eq!(state.row_offset, 37);
+        state.scroll_cursor_to_top();
+        assert_eq!(state.row_offset, 49);
+        state.scroll_cursor_to_bottom();
+        assert_eq!(state.row_offset, 26);
+
+        // Near the top there is nothing to scroll above the first line.
+        state.cursor_y = 3;
+        state.scroll_cursor_to_center();
+        assert_eq!(state.row_offset, 0);
+        assert_eq!(state.cursor_y, 3);
+    }
+
+    #[test]
+    fn test_rows_are_drawn_from_row_offset() {
+        let mut state = EditorState::with_size(2, 10);
+        state.rows = vec!["one".to_string(), "two".to_string(), "three".to_string()];
+        state.row_offset = 1;
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        // The file doesn't fit, so the last column holds the scrollbar.
+        let expected = "two\x1B[K\x1B[10G░\r\nthree\x1B[K\x1B[10G█\r\n";
+        assert_eq!(buf.as_bytes(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_scroll_horizontally_reveals_long_line() {
+        let mut state = EditorState::with_size(1, 80);
+        let line: String = (0..200).map(|n| char::from(b'a' + (n % 26) as u8)).collect();
+        state.rows = vec![line.clone()];
+        state.cursor_x = 150;
+
+        state.scroll();
+        assert_eq!(state.col_offset, 71);
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+        let expected = format!("{}\x1B[K\r\n", &line[71..151]);
+        assert_eq!(buf.as_bytes(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_cursor_after_tab_is_placed_at_rendered_column() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a\tb".to_string()];
+        state.cursor_x = 2;
+
+        state.scroll();
+
+        assert_eq!(state.cursor_position_sequence(), "\x1B[1;9H");
+    }
+
+    #[test]
+    fn test_insert_char_into_empty_buffer() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.insert_char('h');
+        state.insert_char('i');
+
+        assert_eq!(state.rows, vec!["hi"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (2, 0));
+    }
+
+    #[test]
+    fn test_insert_char_mid_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ac".to_string()];
+        state.cursor_x = 1;
+
+        state.insert_char('b');
+
+        assert_eq!(state.rows, vec!["abc"]);
+        assert_eq!(state.cursor_x, 2);
+    }
+
+    #[test]
+    fn test_insert_versus_overwrite_mid_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+        state.cursor_x = 1;
+        state.insert_char('X');
+        assert_eq!(state.rows, vec!["aXbc"]);
+
+        state.rows = vec!["abc".to_string()];
+        state.cursor_x = 1;
+        state.overwrite = true;
+        state.insert_char('X');
+        assert_eq!(state.rows, vec!["aXc"]);
+        assert_eq!(state.cursor_x, 2);
+
+        state.undo();
+        assert_eq!(state.rows, vec!["abc"]);
+    }
+
+    #[test]
+    fn test_overwrite_replaces_tab_and_wide_char_whole_and_appends_at_end() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["\t中".to_string()];
+        state.overwrite = true;
+
+        for c in "xyz".chars() {
+            state.insert_char(c);
+        }
+
+        assert_eq!(state.rows, vec!["xyz"]);
+    }
+
+    #[test]
+    fn test_duplicate_line_copies_below_as_one_undo_unit() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.cursor_y = 1;
+        state.cursor_x = 1;
+
+        state.duplicate_line();
+
+        assert_eq!(state.rows, vec!["ab", "cd", "cd"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 2));
+
+        state.undo();
+        assert_eq!(state.rows, vec!["ab", "cd"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 1));
+    }
+
+    #[test]
+    fn test_move_line_up_and_down_swaps_rows() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        state.cursor_y = 1;
+        state.cursor_x = 1;
+
+        state.move_line_up();
+        assert_eq!(state.rows, vec!["b", "a", "c"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 0));
+        assert!(state.dirty);
+
+        // Already at the top.
+        state.move_line_up();
+        assert_eq!(state.rows, vec!["b", "a", "c"]);
+        assert!(state.bell_pending);
+
+        state.move_line_down();
+        state.move_line_down();
+        assert_eq!(state.rows, vec!["a", "c", "b"]);
+        assert_eq!(state.cursor_y, 2);
+
+        state.undo();
+        assert_eq!(state.rows, vec!["a", "b", "c"]);
+        assert_eq!(state.cursor_y, 1);
+    }
+
+    #[test]
+    fn test_delete_char_within_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+        state.cursor_x = 2;
+
+        state.delete_char();
+
+        assert_eq!(state.rows, vec!["ac"]);
+        assert_eq!(state.cursor_x, 1);
+    }
+
+    #[test]
+    fn test_delete_char_at_line_start_joins_lines() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["foo".to_string(), "bar".to_string()];
+        state.cursor_y = 1;
+
+        state.delete_char();
+
+        assert_eq!(state.rows, vec!["foobar"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (3, 0));
+    }
+
+    #[test]
+    fn test_delete_char_at_start_of_file_is_noop() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+
+        state.delete_char();
+
+        assert_eq!(state.rows, vec!["abc"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 0));
+    }
+
+    #[test]
+    fn test_delete_char_forward() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.cursor_x = 1;
+
+        state.delete_char_forward();
+        assert_eq!(state.rows, vec!["a", "cd"]);
+
+        state.delete_char_forward();
+        assert_eq!(state.rows, vec!["acd"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 0));
+    }
+
+    #[test]
+    fn test_insert_newline_splits_mid_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["hello world".to_string()];
+        state.cursor_x = 5;
+
+        state.insert_newline();
+
+        assert_eq!(state.rows, vec!["hello", " world"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+    }
+
+    #[test]
+    fn test_insert_newline_at_line_end_adds_empty_row() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string(), "def".to_string()];
+        state.cursor_x = 3;
+
+        state.insert_newline();
+
+        assert_eq!(state.rows, vec!["abc", "", "def"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+    }
+
+    #[test]
+    fn test_insert_newline_at_line_start_inserts_row_above() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+
+        state.insert_newline();
+
+        assert_eq!(state.rows, vec!["", "abc"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+    }
+
+    #[test]
+    fn test_dirty_flag_across_open_edit_save() {
+        let path = temp_path("dirty-flag");
+        fs::write(&path, "text\n").unwrap();
+
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        assert!(!state.dirty);
+
+        state.insert_char('!');
+        assert!(state.dirty);
+
+        state.save().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn test_noop_delete_leaves_buffer_clean() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+
+        state.delete_char();
+
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn test_status_bar_shows_name_line_count_and_position() {
+        let mut state = EditorState::with_size(24, 60);
+        state.filename = Some(PathBuf::from("notes.txt"));
+        state.rows = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        state.cursor_y = 1;
+        state.dirty = true;
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_status(&mut buf, state.active);
+        let bar = String::from_utf8(buf.as_bytes().to_vec()).unwrap();
+
+        let text = "notes.txt - 3 lines (modified)";
+        let position = "NORMAL | no ft | 2/3";
+        let padding = " ".repeat(60 - text.len() - position.len());
+        let expected = format!("\x1B[7m{}{}{}\x1B[m\r\n", text, padding, position);
+        assert_eq!(bar, expected);
+    }
+
+    #[test]
+    fn test_mode_label_shows_overwrite_only_in_insert_mode() {
+        let mut state = EditorState::with_size(24, 80);
+        state.overwrite = true;
+        assert_eq!(state.mode_label(), "NORMAL");
+
+        state.mode = Mode::Insert;
+        assert_eq!(state.mode_label(), "REPLACE");
+        state.overwrite = false;
+        state.recording = Some(Vec::new());
+        assert_eq!(state.mode_label(), "INSERT REC");
+    }
+
+    #[test]
+    fn test_status_bar_without_filename() {
+        let state = EditorState::with_size(24, 40);
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_status(&mut buf, state.active);
+        let bar = String::from_utf8(buf.as_bytes().to_vec()).unwrap();
+
+        assert!(bar.contains("[No Name] - 0 lines"));
+        assert!(!bar.contains("(modified)"));
+    }
+
+    #[test]
+    fn test_message_bar_shows_fresh_message() {
+        let mut state = EditorState::with_size(24, 80);
+        state.set_status_message("HELP: Ctrl-S = save".to_string());
+
+        let mut buf = AppendBuffer::new();
+        state.draw_message_bar(&mut buf);
+
+        assert_eq!(buf.as_bytes(), b"\x1B[KHELP: Ctrl-S = save");
+    }
+
+    #[test]
+    fn test_message_bar_suppresses_expired_message() {
+        let mut state = EditorState::with_size(24, 80);
+        state.set_status_message("old news".to_string());
+        state.status_msg_time = Instant::now() - STATUS_MSG_TIMEOUT - Duration::from_secs(1);
+
+        let mut buf = AppendBuffer::new();
+        state.draw_message_bar(&mut buf);
+
+        assert_eq!(buf.as_bytes(), b"\x1B[K");
+    }
+
+    #[test]
+    fn test_assign_filename_from_prompt_answer() {
+        let path = temp_path("save-as");
+        let mut state = EditorState::with_size(24, 80);
+        state.insert_char('x');
+
+        assert!(!state.assign_filename(None));
+        assert_eq!(state.filename, None);
+        assert!(state.dirty);
+
+        assert!(state.assign_filename(Some(path.display().to_string())));
+        assert_eq!(state.filename.as_deref(), Some(path.as_path()));
+
+        // The chosen name is remembered, so a plain save now succeeds.
+        state.save().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "x\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_request_quit_on_clean_buffer_quits_immediately() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.request_quit();
+
+        assert!(state.quit);
+    }
+
+    #[test]
+    fn test_request_quit_counts_down_while_dirty() {
+        let mut state = EditorState::with_size(24, 80);
+        state.insert_char('x');
+
+        for remaining in (0..QUIT_TIMES).rev() {
+            state.request_quit();
+            assert!(!state.quit);
+            assert_eq!(state.quit_times, remaining);
+        }
+
+        state.request_quit();
+        assert!(state.quit);
+    }
+
+    #[test]
+    fn test_insert_and_delete_multibyte_char() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["mnchen".to_string()];
+        state.cursor_x = 1;
+
+        state.insert_char('ü');
+        assert_eq!(state.rows, vec!["münchen"]);
+        assert_eq!(state.cursor_x, 2);
+
+        state.delete_char();
+        assert_eq!(state.rows, vec!["mnchen"]);
+        assert_eq!(state.cursor_x, 1);
+    }
+
+    #[test]
+    fn test_wide_char_advances_rendered_cursor_by_two() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.insert_char('中');
+        state.insert_char('x');
+        assert_eq!(state.rows, vec!["中x"]);
+        assert_eq!(state.cursor_x, 2);
+        assert_eq!(state.cursor_rx(), 3);
+
+        state.move_cursor(Key::ArrowLeft);
+        state.delete_char();
+        assert_eq!(state.rows, vec!["x"]);
+        assert_eq!((state.cursor_x, state.cursor_rx()), (0, 0));
+    }
+
+    #[test]
+    fn test_delete_forward_and_split_on_multibyte_row() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["aüb".to_string()];
+        state.cursor_x = 1;
+
+        state.delete_char_forward();
+        assert_eq!(state.rows, vec!["ab"]);
+
+        state.insert_char('é');
+        state.insert_newline();
+        assert_eq!(state.rows, vec!["aé", "b"]);
+    }
+
+    #[test]
+    fn test_open_selects_syntax_shown_in_status_bar() {
+        let path = temp_path("syntax.rs");
+        fs::write(&path, "fn main() {}\n").unwrap();
+
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(state.syntax.map(|syntax| syntax.name), Some("rust"));
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_status(&mut buf, state.active);
+        let bar = String::from_utf8(buf.as_bytes().to_vec()).unwrap();
+        assert!(bar.contains("rust | 1/1"));
+    }
+
+    #[test]
+    fn test_edit_rehighlights_only_the_edited_row() {
+        let mut state = EditorState::with_size(24, 80);
+        state.syntax = select_syntax("x.rs");
+        state.rows = (0..10).map(|n| format!("let x{n} = {n};")).collect();
+        assert_eq!(state.buffers[0].update_highlights(TAB_STOP), 10);
+
+        state.cursor_y = 5;
+        state.insert_char('y');
+
+        assert_eq!(state.buffers[0].update_highlights(TAB_STOP), 1);
+        // "ylet" is no longer a keyword.
+        assert_eq!(state.highlights[5].hl[..4], [Highlight::Normal; 4]);
+        assert_eq!(state.highlights[4].hl[0], Highlight::Keyword);
+    }
+
+    #[test]
+    fn test_rehighlight_stops_once_comment_state_settles() {
+        let mut state = EditorState::with_size(24, 80);
+        state.syntax = select_syntax("x.rs");
+        state.rows = vec!["a", "b", "c */", "d", "e"].into_iter().map(String::from).collect();
+        state.buffers[0].update_highlights(TAB_STOP);
+
+        // Opening a comment on row 1 reaches the row that closes it.
+        state.cursor_y = 1;
+        state.paste_text("/*");
+        assert_eq!(state.buffers[0].update_highlights(TAB_STOP), 2);
+        assert_eq!(state.highlights[2].hl[0], Highlight::Comment);
+        assert_eq!(state.highlights[3].hl[0], Highlight::Normal);
+
+        // A row inserted and removed keeps the cache in step.
+        state.cursor_y = 3;
+        state.insert_newline();
+        assert_eq!(state.buffers[0].update_highlights(TAB_STOP), 2);
+        state.undo();
+        state.update_highlights();
+        assert_eq!(state.highlights.len(), 5);
+        assert_eq!(state.highlights[4].hl[0], Highlight::Normal);
+    }
+
+    #[test]
+    fn test_block_comment_highlight_follows_edits() {
+
+        let mut state = EditorState::with_size(24, 80);
+        state.syntax = select_syntax("x.rs");
+        state.rows = vec!["/* start".to_string(), "end */ x".to_string(), "y".to_string()];
+
+        state.update_highlights();
+        assert_eq!(state.highlights[1].hl[0], Highlight::Comment);
+        assert_eq!(state.highlights[1].hl[7], Highlight::Normal);
+
+        // Breaking the opening delimiter uncomments the following row.
+        state.cursor_x = 1;
+        state.insert_char(' ');
+        state.update_highlights();
+        assert!(!state.highlights[0].open_comment);
+        assert_eq!(state.highlights[1].hl[0], Highlight::Normal);
+    }
+
+    #[test]
+    fn test_gutter_width_follows_line_count() {
+        let mut state = EditorState::with_size(24, 80);
+        assert_eq!(state.gutter_width(), 0);
+
+        state.show_line_numbers = true;
+        for (lines, width) in [(9, 2), (10, 3), (1000, 5)] {
+            state.rows = vec![String::new(); lines];
+            assert_eq!(state.gutter_width(), width);
+        }
+    }
+
+    #[test]
+    fn test_relative_and_hybrid_line_number_labels() {
+        // Cursor on line 5, which is row 4.
+        let labels = |absolute, relative| -> Vec<String> {
+            (2..7).map(|row| line_number_label(row, 4, absolute, relative)).collect()
+        };
+
+        assert_eq!(labels(true, false), ["3", "4", "5", "6", "7"]);
+        assert_eq!(labels(false, true), ["2", "1", "0", "1", "2"]);
+        assert_eq!(labels(true, true), ["2", "1", "5", "1", "2"]);
+    }
+
+    #[test]
+    fn test_relative_numbers_alone_show_gutter() {
+        let mut state = EditorState::with_size(3, 10);
+        state.relative_line_numbers = true;
+        state.rows = vec!["a".to_string(), "b".to_string()];
+        state.cursor_y = 1;
+        let mut buf = AppendBuffer::new();
+
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        let out = String::from_utf8_lossy(buf.as_bytes()).into_owned();
+        assert_eq!(out, "1 a\x1B[K\r\n0 b\x1B[K\r\n  ~\x1B[K\r\n");
+    }
+
+    #[test]
+    fn test_gutter_numbers_rows_and_blanks_past_end() {
+        let mut state = EditorState::with_size(3, 10);
+        state.show_line_numbers = true;
+        state.rows = vec!["a".to_string(), "b".to_string()];
+        let mut buf = AppendBuffer::new();
+
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        let out = String::from_utf8_lossy(buf.as_bytes()).into_owned();
+        assert_eq!(out, "1 a\x1B[K\r\n2 b\x1B[K\r\n  ~\x1B[K\r\n");
+        assert_eq!(state.cursor_position_sequence(), "\x1B[1;3H");
+    }
+
+    #[test]
+    fn test_scroll_accounts_for_gutter() {
+        let mut state = EditorState::with_size(5, 10);
+        state.show_line_numbers = true;
+        state.rows = vec!["0123456789".to_string()];
+        state.cursor_x = 8;
+
+        state.scroll();
+
+        // Two gutter columns leave eight for text, so column 8 needs a scroll.
+        assert_eq!(state.col_offset, 1);
+    }
+
+    #[test]
+    fn test_click_accounts_for_offsets_and_gutter() {
+        let mut state = EditorState::with_size(5, 20);
+        state.show_line_numbers = true;
+        state.rows = (0..10).map(|i| format!("line {i} with text")).collect();
+        state.row_offset = 3;
+        state.col_offset = 2;
+
+        // Column 6 is the fourth text cell after the 3-wide gutter.
+        state.click(6, 2);
+        assert_eq!((state.cursor_x, state.cursor_y), (4, 4));
+
+        // Past the end of the line clamps to its length.
+        state.click(20, 1);
+        assert_eq!((state.cursor_x, state.cursor_y), (16, 3));
+
+        // The status bar is not part of the text area.
+        state.click(1, 6);
+        assert_eq!((state.cursor_x, state.cursor_y), (16, 3));
+    }
+
+    #[test]
+    fn test_scroll_view_keeps_cursor_on_screen() {
+        let mut state = EditorState::with_size(10, 80);
+        state.rows = vec!["x".to_string(); 50];
+        state.cursor_y = 5;
+
+        state.scroll_view(-3);
+        assert_eq!(state.row_offset, 0);
+
+        state.scroll_view(3);
+        assert_eq!(state.row_offset, 3);
+        state.scroll_view(30);
+        assert_eq!(state.row_offset, 5);
+
+        state.cursor_y = 49;
+        state.row_offset = 40;
+        state.scroll_view(-30);
+        assert_eq!(state.row_offset, 40);
+    }
+
+    #[test]
+    fn test_cut_then_paste_round_trip() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["one".to_string(), "two".to_string(), "three".to_string()];
+
+        state.cut_line();
+        assert_eq!(state.rows, vec!["two", "three"]);
+        assert_eq!(state.clipboard, "one");
+
+        state.cursor_y = 1;
+        state.paste_line();
+        assert_eq!(state.rows, vec!["two", "three", "one"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 2));
+    }
+
+    #[test]
+    fn test_cut_only_line_leaves_empty_row() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["only".to_string()];
+
+        state.cut_line();
+        assert_eq!(state.rows, vec![""]);
+        assert_eq!(state.cursor_y, 0);
+
+        state.undo();
+        assert_eq!(state.rows, vec!["only"]);
+    }
+
+    #[test]
+    fn test_copy_and_paste_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["dup".to_string()];
+
+        state.copy_line();
+        state.paste_line();
+
+        assert_eq!(state.rows, vec!["dup", "dup"]);
+    }
+
+    #[test]
+    fn test_selection_is_drawn_in_reverse_video() {
+        let mut state = EditorState::with_size(1, 10);
+        state.rows = vec!["abcd".to_string()];
+        state.cursor_x = 1;
+        state.toggle_selection();
+        state.cursor_x = 3;
+        let mut buf = AppendBuffer::new();
+
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        assert_eq!(buf.as_bytes(), b"a\x1B[7mbc\x1B[27m\x1B[39md\x1B[K\r\n");
+    }
+
+    #[test]
+    fn test_copy_selection_pastes_as_lines() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.cursor_x = 1;
+        state.toggle_selection();
+        state.cursor_y = 1;
+
+        state.copy_line();
+        assert_eq!(state.selection_anchor, None);
+        state.paste_line();
+
+        assert_eq!(state.rows, vec!["ab", "cd", "b", "c"]);
+    }
+
+    #[test]
+    fn test_goto_line_clamps_to_buffer() {
+        let mut state = EditorState::with_size(5, 80);
+        state.rows = vec!["x".to_string(); 10];
+
+        state.goto_line(9999);
+        assert_eq!(state.cursor_y, 9);
+        assert_eq!(state.row_offset, 9);
+
+        state.goto_line(3);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 2));
+
+        state.goto_line(0);
+        assert_eq!(state.cursor_y, 0);
+    }
+
+    #[test]
+    fn test_soft_tab_reaches_next_tab_stop() {
+        let mut state = EditorState::with_size(24, 80);
+        state.config.expand_tabs = true;
+        state.config.tab_width = 4;
+
+        state.insert_tab();
+        assert_eq!(state.rows, vec!["    "]);
+
+        state.rows = vec!["ab".to_string()];
+        state.cursor_x = 2;
+        state.insert_tab();
+        assert_eq!(state.rows, vec!["ab  "]);
+        assert_eq!(state.cursor_x, 4);
+    }
+
+    #[test]
+    fn test_backspace_removes_soft_tab_indent() {
+        let mut state = EditorState::with_size(24, 80);
+        state.config.expand_tabs = true;
+        state.config.tab_width = 4;
+        state.rows = vec!["      x".to_string()];
+        state.cursor_x = 6;
+
+        state.backspace();
+        assert_eq!(state.rows, vec!["    x"]);
+        state.backspace();
+        assert_eq!(state.rows, vec!["x"]);
+
+        // Outside indentation, Backspace deletes a single character.
+        state.rows = vec!["a   ".to_string()];
+        state.cursor_x = 4;
+        state.backspace();
+        assert_eq!(state.rows, vec!["a  "]);
+    }
+
+    #[test]
+    fn test_hard_tab_without_expand_tabs() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.insert_tab();
+
+        assert_eq!(state.rows, vec!["\t"]);
+    }
+
+    #[test]
+    fn test_newline_copies_indent() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["    foo()".to_string()];
+        state.cursor_x = 9;
+
+        state.insert_newline();
+
+        assert_eq!(state.rows, vec!["    foo()", "    "]);
+        assert_eq!((state.cursor_x, state.cursor_y), (4, 1));
+
+        state.undo();
+        assert_eq!(state.rows, vec!["    foo()"]);
+    }
+
+    #[test]
+    fn test_newline_inside_indent_copies_full_indent() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["\t  x".to_string()];
+        state.cursor_x = 1;
+
+        state.insert_newline();
+
+        assert_eq!(state.rows, vec!["\t", "\t    x"]);
+        assert_eq!(state.cursor_x, 3);
+    }
+
+    #[test]
+    fn test_switching_buffers_keeps_cursor_per_buffer() {
+        let mut state = EditorState::with_size(5, 80);
+        state.rows = vec!["first".to_string(); 20];
+        state.cursor_x = 3;
+        state.cursor_y = 12;
+        state.scroll();
+
+        state.add_buffer();
+        assert_eq!(state.active, 1);
+        assert_eq!((state.cursor_x, state.cursor_y, state.row_offset), (0, 0, 0));
+        state.insert_char('x');
+
+        state.next_buffer();
+        assert_eq!(state.active, 0);
+        assert_eq!((state.cursor_x, state.cursor_y, state.row_offset), (3, 12, 8));
+        assert!(!state.dirty);
+        assert!(state.any_dirty());
+
+        state.prev_buffer();
+        assert_eq!(state.rows, vec!["x"]);
+        assert_eq!(state.cursor_x, 1);
+    }
+
+    #[test]
+    fn test_open_in_new_buffer_reuses_scratch_buffer() {
+        let first = temp_path("buffers-first.txt");
+        let second = temp_path("buffers-second.txt");
+        fs::write(&first, "one\n").unwrap();
+        fs::write(&second, "two\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+
+        state.open_in_new_buffer(&first);
+        state.open_in_new_buffer(&second);
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+
+        assert_eq!(state.buffers.len(), 2);
+        assert_eq!(state.active, 1);
+        assert_eq!(state.buffers[0].rows, vec!["one"]);
+        assert_eq!(state.rows, vec!["two"]);
+    }
+
+    #[test]
+    fn test_open_missing_file_starts_named_buffer() {
+        let path = temp_path("buffers-missing.rs");
+        let mut state = EditorState::with_size(24, 80);
+
+        state.open_in_new_buffer(&path);
+
+        assert_eq!(state.filename.as_deref(), Some(path.as_path()));
+        assert!(state.rows.is_empty());
+        assert_eq!(state.syntax.map(|syntax| syntax.name), Some("rust"));
+    }
+
+    #[test]
+    fn test_pane_heights_share_odd_rows() {
+        // 23 rows: 11 for each pane plus the upper pane's status bar.
+        assert_eq!(pane_heights(23), (11, 11));
+        // 24 rows: the upper pane gets the odd row out.
+        assert_eq!(pane_heights(24), (12, 11));
+        assert_eq!(pane_heights(0), (0, 0));
+    }
+
+    #[test]
+    fn test_current_line_wrapped_in_background() {
+        let mut state = EditorState::with_size(3, 10);
+        state.highlight_current_line = true;
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.cursor_y = 1;
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        let out = String::from_utf8_lossy(buf.as_bytes()).into_owned();
+        assert_eq!(out, "ab\x1B[K\r\n\x1B[40mcd\x1B[K\x1B[m\r\n~\x1B[K\r\n");
+    }
+
+    #[test]
+    fn test_close_middle_buffer_focuses_next() {
+        let mut state = EditorState::with_size(24, 80);
+        for name in ["b", "c", "d"] {
+            state.add_buffer();
+            state.rows = vec![name.to_string()];
+        }
+        state.buffers[0].rows = vec!["a".to_string()];
+        state.active = 1;
+        state.split = Some(Split { other: 3, focus_below: false });
+        state.build_return = Some(2);
+
+        state.close_buffer();
+
+        let rows: Vec<_> = state.buffers.iter().map(|buffer| buffer.rows[0].as_str()).collect();
+        assert_eq!(rows, vec!["a", "c", "d"]);
+        assert_eq!(state.active, 1);
+        assert_eq!(state.rows, vec!["c"]);
+        assert_eq!(state.split.map(|split| split.other), Some(2));
+        assert_eq!(state.build_return, Some(1));
+    }
+
+    #[test]
+    fn test_close_dirty_buffer_needs_confirmation() {
+        let mut state = EditorState::with_size(24, 80);
+        state.add_buffer();
+        state.insert_char('x');
+
+        state.close_buffer();
+        assert_eq!(state.buffers.len(), 2);
+        assert!(state.status_msg.starts_with("WARNING"));
+
+        state.close_buffer();
+        assert_eq!(state.buffers.len(), 1);
+        assert_eq!(state.active, 0);
+    }
+
+    #[test]
+    fn test_close_last_buffers_wraps_then_quits() {
+        let mut state = EditorState::with_size(24, 80);
+        state.add_buffer();
+
+        state.close_buffer();
+        assert_eq!((state.active, state.buffers.len()), (0, 1));
+        assert!(!state.quit);
+
+        state.close_buffer();
+        assert!(state.quit);
+    }
+
+    #[test]
+    fn test_split_panes_edit_only_the_focused_buffer() {
+        let mut state = EditorState::with_size(23, 80);
+        state.add_buffer();
+        state.rows = vec!["second".to_string()];
+        state.active = 0;
+
+        // The tab bar takes the top row while two buffers are open.
+        state.toggle_split();
+        state.insert_char('a');
+        assert_eq!(state.cursor_position_sequence(), "\x1B[2;2H");
+
+        state.switch_pane();
+        assert_eq!(state.active, 1);
+        state.insert_char('b');
+        assert_eq!(state.cursor_position_sequence(), "\x1B[14;2H");
+
+        assert_eq!(state.buffers[0].rows, vec!["a"]);
+        assert_eq!(state.buffers[1].rows, vec!["bsecond"]);
+        assert_eq!(state.text_rows(), 10);
+    }
+
+    #[test]
+    fn test_crlf_file_is_saved_with_crlf() {
+        let path = temp_path("crlf.txt");
+        fs::write(&path, "one\r\ntwo\r\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+
+        state.open(&path).unwrap();
+        assert_eq!(state.line_ending, LineEnding::Crlf);
+        state.cursor_y = 1;
+        state.insert_char('x');
+        state.save().unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, "one\r\nxtwo\r\n");
+    }
+
+    #[test]
+    fn test_toggle_line_ending_converts_on_save() {
+        let path = temp_path("to-crlf.txt");
+        fs::write(&path, "a\nb\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+
+        state.open(&path).unwrap();
+        state.toggle_line_ending();
+        assert!(state.dirty);
+        state.save().unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_detect_dominant_line_ending() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\r\nb\nc\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect(""), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_open_then_save_round_trips_final_newline() {
+        for (name, text) in [
+            ("empty", ""),
+            ("one-line", "only\n"),
+            ("blank-line", "\n"),
+            ("unterminated", "a\nb"),
+            ("crlf-unterminated", "a\r\nb"),
+        ] {
+            let path = temp_path(&format!("round-trip-{}", name));
+            fs::write(&path, text).unwrap();
+            let mut state = EditorState::with_size(24, 80);
+
+            state.open(&path).unwrap();
+            state.save().unwrap();
+            let saved = fs::read_to_string(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(saved, text, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_matching_brackets_are_highlighted() {
+        use Highlight::{MatchBracket as M, Normal as N};
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a(b)c".to_string()];
+        state.cursor_x = 1;
+
+        let brackets = state.matched_brackets(0);
+
+        assert_eq!(brackets, vec![(0, 1), (0, 3)]);
+        assert_eq!(state.drawn_highlight(0, 0, &brackets), vec![N, M, N, M]);
+
+        state.cursor_x = 2;
+        assert!(state.matched_brackets(0).is_empty());
+    }
+
+    #[test]
+    fn test_save_strips_trailing_whitespace_when_configured() {
+        let path = temp_path("strip-on-save.txt");
+        let mut state = EditorState::with_size(24, 80);
+        state.config.strip_trailing_whitespace_on_save = true;
+        state.filename = Some(path.clone());
+        state.rows = vec!["a \t".to_string(), "b".to_string()];
+        state.cursor_y = 1;
+
+        state.save().unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, "a\nb\n");
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn test_trailing_whitespace_is_highlighted() {
+        use Highlight::{Normal as N, TrailingWhitespace as W};
+        let mut state = EditorState::with_size(24, 80);
+        state.config.highlight_trailing_whitespace = true;
+        state.rows = vec!["a b  ".to_string(), "c ".to_string()];
+        state.cursor_y = 1;
+        state.cursor_x = 2;
+
+        assert_eq!(state.drawn_highlight(0, 0, &[]), vec![N, N, N, W, W]);
+        assert_eq!(state.drawn_highlight(0, 1, &[]), vec![N, N]);
+    }
+
+    #[test]
+    fn test_reload_replaces_rows_after_external_change() {
+        let path = temp_path("reload.txt");
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        state.cursor_y = 2;
+        state.cursor_x = 4;
+
+        fs::write(&path, "new\n").unwrap();
+        state.reload();
+
+        assert_eq!(state.rows, vec!["new"]);
+        assert_eq!((state.cursor_y, state.cursor_x), (0, 3));
+        assert_eq!(state.status_msg, "Reloaded: 1 lines");
+
+        // Unsaved changes need a second press.
+        state.insert_char('x');
+        fs::write(&path, "newer\n").unwrap();
+        state.reload();
+        assert_eq!(state.rows, vec!["newx"]);
+        state.reload();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(state.rows, vec!["newer"]);
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn test_save_over_file_changed_on_disk_needs_confirmation() {
+        let path = temp_path("changed-on-disk.txt");
+        fs::write(&path, "ours\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+        state.open(&path).unwrap();
+        state.insert_char('x');
+
+        fs::write(&path, "theirs\n").unwrap();
+        let bumped = state.mtime.unwrap() + Duration::from_secs(10);
+        File::options().write(true).open(&path).unwrap().set_modified(bumped).unwrap();
+        assert!(state.changed_on_disk());
+
+        state.save_with_prompt(&mut io::empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "theirs\n");
+        assert!(state.status_msg.starts_with("File changed on disk"));
+
+        state.save_with_prompt(&mut io::empty());
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(saved, "xours\n");
+        assert!(!state.changed_on_disk());
+    }
+
+    #[test]
+    fn test_search_matches_are_highlighted() {
+        use Highlight::{CurrentMatch as C, Normal as N, SearchMatch as S};
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab ab".to_string()];
+        state.cursor_x = 3;
+        state.search_pattern = Some(SearchPattern::Plain("ab".to_string()));
+
+        assert_eq!(state.drawn_highlight(0, 0, &[]), vec![S, S, N, C, C]);
+
+        state.search_pattern = None;
+        assert_eq!(state.drawn_highlight(0, 0, &[]), vec![]);
+    }
+
+    #[test]
+    fn test_control_chars_are_drawn_in_reverse_video() {
+        use Highlight::{Control as R, Normal as N};
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a\x01b".to_string()];
+
+        assert_eq!(state.drawn_highlight(0, 0, &[]), vec![N, R, R]);
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+        let first_line = String::from_utf8(buf.as_bytes().to_vec()).unwrap();
+        assert!(first_line.starts_with("a\x1B[7m^A\x1B[27m\x1B[39mb"));
+    }
+}
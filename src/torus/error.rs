@@ -0,0 +1,41 @@
+use std::{error, fmt, io};
+
+/// Errors surfaced by the editor's I/O, rendering and config handling.
+#[derive(Debug)]
+pub enum EditorError {
+    Io(io::Error),
+    /// Input that could not be understood, such as a malformed config file
+    /// or terminal report.
+    Parse(String),
+}
+
+impl fmt::Display for EditorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditorError::Io(err) => write!(f, "{}", err),
+            EditorError::Parse(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for EditorError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            EditorError::Io(err) => Some(err),
+            EditorError::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for EditorError {
+    fn from(err: io::Error) -> Self {
+        EditorError::Io(err)
+    }
+}
+
+impl EditorError {
+    /// True if this is an I/O error of the given kind.
+    pub fn is_io_kind(&self, kind: io::ErrorKind) -> bool {
+        matches!(self, EditorError::Io(err) if err.kind() == kind)
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use super::editor::EditorState;
+
+/// Runs `command` through the shell with `input` on its stdin and returns
+/// its stdout. A command that can't be started or exits with an error
+/// gives a message for the status bar, taken from its stderr if it wrote
+/// any.
+pub fn run_filter(command: &str, input: &str) -> Result<String, String> {
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Can't run {}: {}", command, err))?;
+
+    // Write from another thread, so a command that fills its stdout pipe
+    // before reading all its input can't deadlock with us.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    let writer = thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+        // Commands that don't read their input close it early.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    });
+
+    let output = child.wait_with_output().map_err(|err| format!("{}: {}", command, err))?;
+    let _ = writer.join();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.split_whitespace().collect::<Vec<_>>().join(" ");
+        return Err(if reason.is_empty() {
+            format!("{}: {}", command, output.status)
+        } else {
+            format!("{}: {}", command, reason)
+        });
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{}: output is not UTF-8", command))
+}
+
+/// A command that runs `command` through the platform's shell.
+#[cfg(unix)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+impl EditorState {
+    /// Asks for a shell command and filters the selected lines, or the
+    /// whole buffer, through it.
+    pub fn filter_with_prompt<R: Read>(&mut self, input: &mut R) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(command) = self.prompt(input, "Pipe through: %s (ESC to cancel)", |_, _, _| {}) else {
+            return;
+        };
+        if !command.trim().is_empty() {
+            self.filter_through(&command);
+        }
+    }
+
+    /// Replaces the rows the selection touches, or every row, with the
+    /// output of `command` given them as input, as one undo unit. On
+    /// failure the buffer is left as it was and the error is shown.
+    pub fn filter_through(&mut self, command: &str) {
+        let (first, last) = match self.selected_rows() {
+            Some((first, last)) => (first, (last + 1).min(self.num_rows())),
+            None => (0, self.num_rows()),
+        };
+        let text: String = self.rows[first..last].iter().map(|row| format!("{}\n", row)).collect();
+
+        let output = match run_filter(command, &text) {
+            Ok(output) => output,
+            Err(err) => {
+                self.set_status_message(err);
+                return;
+            }
+        };
+
+        self.selection_anchor = None;
+        self.replace_rows(first..last, output.lines().map(str::to_string).collect());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod filter_tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_buffer_through_tr() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string(), "Def".to_string()];
+
+        state.filter_through("tr a-z A-Z");
+
+        assert_eq!(state.rows, vec!["ABC", "DEF"]);
+        state.undo();
+        assert_eq!(state.rows, vec!["abc", "Def"]);
+    }
+
+    #[test]
+    fn test_filter_selected_lines_only() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["top".to_string(), "c".to_string(), "a".to_string(), "b".to_string()];
+        state.cursor_y = 1;
+        state.toggle_selection();
+        state.cursor_y = 3;
+        state.cursor_x = 1;
+
+        state.filter_through("sort");
+
+        assert_eq!(state.rows, vec!["top", "a", "b", "c"]);
+        assert_eq!(state.selection_anchor, None);
+    }
+
+    #[test]
+    fn test_failing_command_leaves_buffer_unchanged() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["text".to_string()];
+
+        state.filter_through("echo oops >&2; exit 3");
+
+        assert_eq!(state.rows, vec!["text"]);
+        assert!(!state.dirty);
+        assert_eq!(state.status_msg, "echo oops >&2; exit 3: oops");
+    }
+}
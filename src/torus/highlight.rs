@@ -0,0 +1,328 @@
+use std::path::Path;
+
+/// Syntax class of a single rendered character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Normal,
+    Number,
+    String,
+    Comment,
+    Keyword,
+    /// Part of the active selection; overrides the syntax class.
+    Selection,
+    /// The bracket under the cursor or its match.
+    MatchBracket,
+    /// Spaces and tabs at the end of a line.
+    TrailingWhitespace,
+    /// A match of the search query.
+    SearchMatch,
+    /// The search match the cursor is on.
+    CurrentMatch,
+    /// A control character shown in caret notation.
+    Control,
+    /// Background of the ruler column.
+    Ruler,
+    /// Background of the cursor line.
+    CurrentLine,
+}
+
+impl Highlight {
+    /// Every class, in declaration order, so `class as usize` indexes it.
+    pub const ALL: [Highlight; 13] = [
+        Highlight::Normal,
+        Highlight::Number,
+        Highlight::String,
+        Highlight::Comment,
+        Highlight::Keyword,
+        Highlight::Selection,
+        Highlight::MatchBracket,
+        Highlight::TrailingWhitespace,
+        Highlight::SearchMatch,
+        Highlight::CurrentMatch,
+        Highlight::Control,
+        Highlight::Ruler,
+        Highlight::CurrentLine,
+    ];
+
+    /// SGR code that undoes this class's attribute when it isn't a plain
+    /// foreground colour, which the next class's colour would replace.
+    pub fn off_code(self) -> Option<u8> {
+        match self {
+            Highlight::Selection | Highlight::Control => Some(27),
+            Highlight::MatchBracket
+            | Highlight::TrailingWhitespace
+            | Highlight::SearchMatch
+            | Highlight::CurrentMatch
+            | Highlight::Ruler
+            | Highlight::CurrentLine => Some(49),
+            _ => None,
+        }
+    }
+}
+
+/// Highlighting rules for one language.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SyntaxDef {
+    /// Name shown in the status bar.
+    pub name: &'static str,
+    /// File extensions (without the dot) that select this language.
+    pub extensions: &'static [&'static str],
+    /// Prefix that starts a comment running to the end of the line.
+    pub line_comment: Option<&'static str>,
+    /// Delimiters of a comment that may span several lines.
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Characters that open (and close) a string literal.
+    pub quotes: &'static str,
+    pub keywords: &'static [&'static str],
+}
+
+/// Highlight of one row, plus whether it ends inside an unterminated block
+/// comment that carries over to the next row.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowHighlight {
+    pub hl: Vec<Highlight>,
+    pub open_comment: bool,
+}
+
+/// Languages the editor knows how to highlight.
+pub static SYNTAX_DB: &[SyntaxDef] = &[
+    SyntaxDef {
+        name: "rust",
+        extensions: &["rs"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        // Single quotes are left out so lifetimes like 'a don't open a string.
+        quotes: "\"",
+        keywords: &[
+            "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+            "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+            "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+            "unsafe", "use", "where", "while",
+        ],
+    },
+    SyntaxDef {
+        name: "c",
+        extensions: &["c", "h", "cpp", "hpp", "cc"],
+        line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
+        quotes: "\"'",
+        keywords: &[
+            "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+            "enum", "extern", "float", "for", "goto", "if", "int", "long", "return", "short",
+            "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned",
+            "void", "volatile", "while",
+        ],
+    },
+    SyntaxDef {
+        name: "python",
+        extensions: &["py"],
+        line_comment: Some("#"),
+        block_comment: None,
+        quotes: "\"'",
+        keywords: &[
+            "and", "as", "assert", "break", "class", "continue", "def", "del", "elif", "else",
+            "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+            "lambda", "None", "not", "or", "pass", "raise", "return", "True", "try", "while",
+            "with", "yield",
+        ],
+    },
+];
+
+/// Picks the syntax definition for a file name by its extension.
+pub fn select_syntax(filename: impl AsRef<Path>) -> Option<&'static SyntaxDef> {
+    let ext = filename.as_ref().extension()?.to_str()?;
+    SYNTAX_DB.iter().find(|syntax| syntax.extensions.contains(&ext))
+}
+
+/// Characters that end a token, so that e.g. the `1` in `x1` is not a number.
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || ",.()+-/*=~%<>[];{}:&|!".contains(c)
+}
+
+/// Returns the length of the keyword starting at `chars[i]`, if one is
+/// there as a whole word.
+fn keyword_at(chars: &[char], i: usize, keywords: &[&str]) -> Option<usize> {
+    keywords.iter().find_map(|kw| {
+        let len = kw.chars().count();
+        let whole_word = chars.get(i + len).is_none_or(|&c| is_separator(c));
+        (row_has_prefix_at(chars, i, kw) && whole_word).then_some(len)
+    })
+}
+
+/// Computes the highlight class of every character in a rendered row.
+/// `in_comment` says whether the previous row ended inside a block comment.
+/// Without a syntax definition only numbers are highlighted.
+pub fn highlight_row(row: &str, syntax: Option<&SyntaxDef>, in_comment: bool) -> RowHighlight {
+    let chars: Vec<char> = row.chars().collect();
+    let mut hl = vec![Highlight::Normal; chars.len()];
+    let mut prev_sep = true;
+    let mut in_string: Option<char> = None;
+    let mut in_comment = in_comment && syntax.is_some_and(|s| s.block_comment.is_some());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let prev_hl = if i > 0 { hl[i - 1] } else { Highlight::Normal };
+
+        if let Some(prefix) = syntax.and_then(|s| s.line_comment)
+            && in_string.is_none()
+            && !in_comment
+            && row_has_prefix_at(&chars, i, prefix)
+        {
+            hl[i..].fill(Highlight::Comment);
+            break;
+        }
+
+        if let Some((start, end)) = syntax.and_then(|s| s.block_comment)
+            && in_string.is_none()
+        {
+            if in_comment {
+                if row_has_prefix_at(&chars, i, end) {
+                    let len = end.chars().count();
+                    hl[i..i + len].fill(Highlight::Comment);
+                    i += len;
+                    in_comment = false;
+                    prev_sep = true;
+                } else {
+                    hl[i] = Highlight::Comment;
+                    i += 1;
+                }
+                continue;
+            }
+            if row_has_prefix_at(&chars, i, start) {
+                let len = start.chars().count();
+                hl[i..i + len].fill(Highlight::Comment);
+                i += len;
+                in_comment = true;
+                continue;
+            }
+        }
+
+        if let Some(quote) = in_string {
+            hl[i] = Highlight::String;
+            if c == '\\' && i + 1 < chars.len() {
+                // An escaped character, including an escaped quote, stays in the string.
+                hl[i + 1] = Highlight::String;
+                i += 2;
+                continue;
+            }
+            if c == quote {
+                in_string = None;
+            }
+            prev_sep = true;
+            i += 1;
+            continue;
+        }
+        if syntax.is_some_and(|s| s.quotes.contains(c)) {
+            in_string = Some(c);
+            hl[i] = Highlight::String;
+            i += 1;
+            continue;
+        }
+
+        if (c.is_ascii_digit() && (prev_sep || prev_hl == Highlight::Number))
+            || (c == '.' && prev_hl == Highlight::Number)
+        {
+            hl[i] = Highlight::Number;
+            prev_sep = false;
+            i += 1;
+            continue;
+        }
+
+        if prev_sep
+            && let Some(len) = syntax.and_then(|s| keyword_at(&chars, i, s.keywords))
+        {
+            hl[i..i + len].fill(Highlight::Keyword);
+            i += len;
+            prev_sep = false;
+            continue;
+        }
+
+        prev_sep = is_separator(c);
+        i += 1;
+    }
+
+    RowHighlight { hl, open_comment: in_comment }
+}
+
+/// Returns true if `prefix` occurs in `chars` starting at index `i`.
+fn row_has_prefix_at(chars: &[char], i: usize, prefix: &str) -> bool {
+    let len = prefix.chars().count();
+    i + len <= chars.len() && prefix.chars().eq(chars[i..i + len].iter().copied())
+}
+
+#[cfg(test)]
+mod highlight_tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_numbers_only() {
+        let hl = highlight_row("abc 123 def", None, false).hl;
+
+        let numbers: Vec<usize> = (0..hl.len()).filter(|&i| hl[i] == Highlight::Number).collect();
+        assert_eq!(numbers, vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn test_highlight_decimal_and_embedded_digits() {
+        use Highlight::{Normal as N, Number as D};
+
+        assert_eq!(highlight_row("3.14", None, false).hl, vec![D, D, D, D]);
+        assert_eq!(highlight_row("x1 (2)", None, false).hl, vec![N, N, N, N, D, N]);
+    }
+
+    #[test]
+    fn test_select_syntax_by_extension() {
+        assert_eq!(select_syntax("main.rs").map(|s| s.name), Some("rust"));
+        assert_eq!(select_syntax("src/lib.c").map(|s| s.name), Some("c"));
+        assert_eq!(select_syntax("script.py").map(|s| s.name), Some("python"));
+        assert_eq!(select_syntax("notes.txt"), None);
+        assert_eq!(select_syntax("Makefile"), None);
+    }
+
+    #[test]
+    fn test_highlight_keywords_and_line_comment() {
+        use Highlight::{Comment as C, Keyword as K, Normal as N};
+        let rust = select_syntax("x.rs");
+
+        assert_eq!(highlight_row("let x", rust, false).hl, vec![K, K, K, N, N]);
+        // Keywords only match whole words.
+        assert_eq!(highlight_row("letx", rust, false).hl, vec![N; 4]);
+        assert_eq!(highlight_row("a // b", rust, false).hl, vec![N, N, C, C, C, C]);
+        // Without a syntax definition there are no keywords or comments.
+        assert_eq!(highlight_row("let // x", None, false).hl, vec![N; 8]);
+    }
+
+    #[test]
+    fn test_highlight_string_with_escaped_quote() {
+        use Highlight::{Normal as N, String as S};
+        let rust = select_syntax("x.rs");
+
+        let hl = highlight_row(r#"x "a\"b" 1"#, rust, false).hl;
+
+        assert_eq!(hl, vec![N, N, S, S, S, S, S, S, N, Highlight::Number]);
+    }
+
+    #[test]
+    fn test_highlight_single_quoted_string() {
+        let c = select_syntax("x.c");
+
+        let hl = highlight_row("'//'", c, false).hl;
+
+        assert_eq!(hl, vec![Highlight::String; 4]);
+    }
+
+    #[test]
+    fn test_block_comment_carries_to_next_row() {
+        use Highlight::{Comment as C, Normal as N};
+        let rust = select_syntax("x.rs");
+
+        let first = highlight_row("x /* a", rust, false);
+        assert_eq!(first.hl, vec![N, N, C, C, C, C]);
+        assert!(first.open_comment);
+
+        let second = highlight_row("b */ y", rust, first.open_comment);
+        assert_eq!(second.hl, vec![C, C, C, C, N, N]);
+        assert!(!second.open_comment);
+    }
+}
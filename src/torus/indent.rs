@@ -0,0 +1,136 @@
+use super::editor::EditorState;
+use super::undo::EditOp;
+
+/// Number of leading chars that one dedent removes from `row`: a single
+/// tab, or up to `tab_width` spaces ended early by a tab.
+pub fn dedent_width(row: &str, tab_width: usize) -> usize {
+    let mut width = 0;
+    for c in row.chars().take(tab_width) {
+        match c {
+            ' ' => width += 1,
+            '\t' => return width + 1,
+            _ => break,
+        }
+    }
+    width
+}
+
+impl EditorState {
+    /// Rows an indent or dedent applies to: those the selection touches,
+    /// or the cursor row. A selection ending at the start of a row leaves
+    /// that row out.
+    fn indent_rows(&self) -> Option<(usize, usize)> {
+        let (first, last) = self.selected_rows().unwrap_or((self.cursor_y, self.cursor_y));
+        let last = last.min(self.num_rows().checked_sub(1)?);
+        (first <= last).then_some((first, last))
+    }
+
+    /// Indents every selected row, or the cursor row, by one tab stop as
+    /// one undo unit, using spaces if `expand_tabs` is set. Empty rows are
+    /// left alone.
+    pub fn indent_selection(&mut self) {
+        let Some((first, last)) = self.indent_rows() else {
+            return;
+        };
+        let unit: Vec<char> = if self.config.expand_tabs {
+            vec![' '; self.config.tab_width]
+        } else {
+            vec!['\t']
+        };
+
+        let mut ops = Vec::new();
+        let mut shifts = Vec::new();
+        for y in first..=last {
+            if self.row_len(y) == 0 {
+                continue;
+            }
+            ops.extend(unit.iter().enumerate().map(|(i, &c)| EditOp::InsertChar { at: (y, i), c }));
+            shifts.push((y, unit.len() as isize));
+        }
+        self.shift_rows(ops, &shifts);
+    }
+
+    /// Removes up to one tab stop of leading whitespace from every selected
+    /// row, or the cursor row, as one undo unit. Rows with no leading
+    /// whitespace are unchanged.
+    pub fn dedent_selection(&mut self) {
+        let Some((first, last)) = self.indent_rows() else {
+            return;
+        };
+
+        let mut ops = Vec::new();
+        let mut shifts = Vec::new();
+        for y in first..=last {
+            let row = &self.rows[y];
+            let width = dedent_width(row, self.config.tab_width);
+            ops.extend(row.chars().take(width).map(|c| EditOp::DeleteChar { at: (y, 0), c }));
+            if width > 0 {
+                shifts.push((y, -(width as isize)));
+            }
+        }
+        self.shift_rows(ops, &shifts);
+    }
+
+    /// Applies an indent or dedent, moving the cursor and selection anchor
+    /// along with the text of the rows in `shifts`.
+    fn shift_rows(&mut self, ops: Vec<EditOp>, shifts: &[(usize, isize)]) {
+        if ops.is_empty() || !self.check_writable() {
+            return;
+        }
+        let shifted = |(y, x): (usize, usize)| {
+            let delta = shifts.iter().find(|&&(row, _)| row == y).map_or(0, |&(_, delta)| delta);
+            (y, x.saturating_add_signed(delta))
+        };
+
+        self.selection_anchor = self.selection_anchor.map(shifted);
+        self.edit_all(ops, shifted((self.cursor_y, self.cursor_x)));
+    }
+}
+
+#[cfg(test)]
+mod indent_tests {
+    use super::*;
+
+    #[test]
+    fn test_dedent_width() {
+        assert_eq!(dedent_width("\tx", 4), 1);
+        assert_eq!(dedent_width("      x", 4), 4);
+        assert_eq!(dedent_width("  \tx", 4), 3);
+        assert_eq!(dedent_width("x", 4), 0);
+    }
+
+    #[test]
+    fn test_indent_and_dedent_two_line_selection() {
+        let mut state = EditorState::with_size(24, 80);
+        state.config.expand_tabs = true;
+        state.config.tab_width = 4;
+        state.rows = vec!["a".to_string(), "  b".to_string(), "c".to_string()];
+        state.selection_anchor = Some((0, 1));
+        (state.cursor_y, state.cursor_x) = (1, 2);
+
+        state.indent_selection();
+        assert_eq!(state.rows, vec!["    a", "      b", "c"]);
+        assert_eq!(state.selection_anchor, Some((0, 5)));
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 6));
+
+        state.dedent_selection();
+        state.dedent_selection();
+        assert_eq!(state.rows, vec!["a", "b", "c"]);
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 0));
+
+        state.undo();
+        assert_eq!(state.rows, vec!["a", "  b", "c"]);
+    }
+
+    #[test]
+    fn test_indent_cursor_row_with_tabs() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a".to_string(), "b".to_string()];
+        state.cursor_y = 1;
+
+        state.indent_selection();
+
+        assert_eq!(state.rows, vec!["a", "\tb"]);
+        assert_eq!(state.cursor_x, 1);
+    }
+}
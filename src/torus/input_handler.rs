@@ -0,0 +1,951 @@
+use std::io::{self, Read};
+use std::time::Instant;
+
+use super::build::BUILD_TITLE;
+use super::editor::{CLOSE_TIMES, EditorState, OVERWRITE_TIMES, RELOAD_TIMES};
+use super::macros::replay_macro;
+use super::mode::{Mode, Operator};
+
+/// A decoded keypress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    CtrlArrowLeft,
+    CtrlArrowRight,
+    AltArrowUp,
+    AltArrowDown,
+    /// Shift-Tab.
+    BackTab,
+    PageUp,
+    PageDown,
+    CtrlPageUp,
+    CtrlPageDown,
+    Home,
+    End,
+    Insert,
+    Delete,
+    Escape,
+    /// Start of a bracketed paste; the pasted text follows on the input.
+    PasteStart,
+    /// End of a bracketed paste, seen on its own only in a recorded macro.
+    PasteEnd,
+    /// An SGR mouse report: 1-based terminal cell and xterm button code.
+    Mouse { x: u16, y: u16, button: u16 },
+    /// A letter or digit typed with Alt (or Meta), sent as ESC and the key.
+    Alt(char),
+    /// The terminal window gained focus (`\x1B[I`).
+    FocusIn,
+    /// The terminal window lost focus (`\x1B[O`).
+    FocusOut,
+    Char(char),
+}
+
+/// SGR button code of a left-button press.
+pub const MOUSE_LEFT: u16 = 0;
+
+/// SGR button codes of the scroll wheel.
+pub const MOUSE_WHEEL_UP: u16 = 64;
+pub const MOUSE_WHEEL_DOWN: u16 = 65;
+
+/// Lines scrolled per wheel notch.
+const WHEEL_LINES: isize = 3;
+
+/// Byte sent by the Backspace key in most terminals.
+pub const BACKSPACE: char = '\x7f';
+
+/// Byte sent by the Enter key once `ICRNL` translation is off.
+pub const ENTER: char = '\r';
+
+/// Maps a letter to the character its Ctrl-chord produces (e.g. `ctrl_key(b's')` is `'\x13'`).
+pub const fn ctrl_key(k: u8) -> char {
+    (k & 0x1f) as char
+}
+
+/// Returns true for keys that should be inserted into the text as typed.
+pub fn is_insertable(c: char) -> bool {
+    c == '\t' || !c.is_control()
+}
+
+/// Waits for a keypress on `input` and decodes it, folding escape sequences
+/// for navigation keys into their `Key` variants. Returns `None` if the read
+/// timed out (or hit end of input) without a key.
+pub fn editor_read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let mut byte = [0; 1];
+    if input.read(&mut byte)? == 0 {
+        return Ok(None);
+    }
+    if byte[0] != 0x1B {
+        return Ok(Some(Key::Char(read_utf8_char(input, byte[0]))));
+    }
+
+    // A lone ESC, a partial sequence or an unknown one is just Escape.
+    Ok(Some(read_escape_sequence(input).unwrap_or(Key::Escape)))
+}
+
+/// Decodes the UTF-8 character starting with `first`, reading however many
+/// continuation bytes its leading bits call for. Malformed or truncated
+/// sequences decode to U+FFFD rather than failing.
+fn read_utf8_char<R: Read>(input: &mut R, first: u8) -> char {
+    let len = match first {
+        0x00..=0x7F => return char::from(first),
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return char::REPLACEMENT_CHARACTER,
+    };
+
+    let mut bytes = [first, 0, 0, 0];
+    if input.read_exact(&mut bytes[1..len]).is_err() {
+        return char::REPLACEMENT_CHARACTER;
+    }
+
+    std::str::from_utf8(&bytes[..len])
+        .ok()
+        .and_then(|s| s.chars().next())
+        .unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// Decodes the remainder of an escape sequence after the leading ESC.
+/// Returns `None` if the input ends early or the sequence is not recognised.
+fn read_escape_sequence<R: Read>(input: &mut R) -> Option<Key> {
+    match read_byte(input).ok()? {
+        b'[' => {}
+        b'O' => {
+            return match read_byte(input).ok()? {
+                b'H' => Some(Key::Home),
+                b'F' => Some(Key::End),
+                _ => None,
+            };
+        }
+        c if c.is_ascii_alphanumeric() => return Some(Key::Alt(char::from(c))),
+        _ => return None,
+    }
+
+    let code = read_byte(input).ok()?;
+    if code == b'<' {
+        return read_sgr_mouse(input);
+    }
+    if code.is_ascii_digit() {
+        let mut number = u32::from(code - b'0');
+        loop {
+            match read_byte(input).ok()? {
+                digit @ b'0'..=b'9' => {
+                    number = number.checked_mul(10)?.checked_add(u32::from(digit - b'0'))?;
+                }
+                // Numeric forms like \x1B[5~ carry a terminating tilde.
+                b'~' => break,
+                // Modified keys like Ctrl-Right arrive as \x1B[1;5C.
+                b';' => return read_modified_key(input, number),
+                _ => return None,
+            }
+        }
+        return match number {
+            1 | 7 => Some(Key::Home),
+            2 => Some(Key::Insert),
+            3 => Some(Key::Delete),
+            4 | 8 => Some(Key::End),
+            5 => Some(Key::PageUp),
+            6 => Some(Key::PageDown),
+            200 => Some(Key::PasteStart),
+            201 => Some(Key::PasteEnd),
+            _ => None,
+        };
+    }
+
+    match code {
+        b'A' => Some(Key::ArrowUp),
+        b'B' => Some(Key::ArrowDown),
+        b'C' => Some(Key::ArrowRight),
+        b'D' => Some(Key::ArrowLeft),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'Z' => Some(Key::BackTab),
+        b'I' => Some(Key::FocusIn),
+        b'O' => Some(Key::FocusOut),
+        _ => None,
+    }
+}
+
+/// Decodes the `5C` tail of a modified key sequence such as `\x1B[1;5C`,
+/// or `5~` for ones with a numeric key code like `\x1B[5;5~`.
+fn read_modified_key<R: Read>(input: &mut R, number: u32) -> Option<Key> {
+    let modifier = read_byte(input).ok()?;
+    match (number, modifier, read_byte(input).ok()?) {
+        (1, b'5', b'C') => Some(Key::CtrlArrowRight),
+        (1, b'5', b'D') => Some(Key::CtrlArrowLeft),
+        (1, b'3', b'A') => Some(Key::AltArrowUp),
+        (1, b'3', b'B') => Some(Key::AltArrowDown),
+        (5, b'5', b'~') => Some(Key::CtrlPageUp),
+        (6, b'5', b'~') => Some(Key::CtrlPageDown),
+        _ => None,
+    }
+}
+
+/// Decodes the `b;x;yM` tail of an SGR mouse report (`\x1B[<b;x;yM`).
+/// Releases end in `m` and decode the same way as presses.
+fn read_sgr_mouse<R: Read>(input: &mut R) -> Option<Key> {
+    let mut fields = [0u16; 3];
+    let mut idx = 0;
+
+    loop {
+        match read_byte(input).ok()? {
+            digit @ b'0'..=b'9' => {
+                let field = &mut fields[idx];
+                *field = field.checked_mul(10)?.checked_add(u16::from(digit - b'0'))?;
+            }
+            b';' if idx < 2 => idx += 1,
+            b'M' | b'm' if idx == 2 => break,
+            _ => return None,
+        }
+    }
+
+    let [button, x, y] = fields;
+    Some(Key::Mouse { x, y, button })
+}
+
+/// Marker that ends a bracketed paste.
+const PASTE_END: &[u8] = b"\x1B[201~";
+
+/// Reads pasted text up to the bracketed-paste end marker (or the end of
+/// input). Invalid UTF-8 is replaced rather than rejected.
+fn read_paste<R: Read>(input: &mut R) -> String {
+    let mut bytes = Vec::new();
+    while let Ok(byte) = read_byte(input) {
+        bytes.push(byte);
+        if bytes.ends_with(PASTE_END) {
+            bytes.truncate(bytes.len() - PASTE_END.len());
+            break;
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The bytes a terminal sends for `key`, which `editor_read_key` decodes
+/// back to it. Escape is sent twice, which still decodes as one Escape but
+/// can't merge with the key after it into an Alt chord.
+pub fn key_bytes(key: Key) -> Vec<u8> {
+    let sequence = match key {
+        Key::ArrowUp => "\x1B[A",
+        Key::ArrowDown => "\x1B[B",
+        Key::ArrowRight => "\x1B[C",
+        Key::ArrowLeft => "\x1B[D",
+        Key::CtrlArrowRight => "\x1B[1;5C",
+        Key::CtrlArrowLeft => "\x1B[1;5D",
+        Key::AltArrowUp => "\x1B[1;3A",
+        Key::AltArrowDown => "\x1B[1;3B",
+        Key::BackTab => "\x1B[Z",
+        Key::PageUp => "\x1B[5~",
+        Key::PageDown => "\x1B[6~",
+        Key::CtrlPageUp => "\x1B[5;5~",
+        Key::CtrlPageDown => "\x1B[6;5~",
+        Key::Home => "\x1B[H",
+        Key::End => "\x1B[F",
+        Key::Insert => "\x1B[2~",
+        Key::Delete => "\x1B[3~",
+        Key::Escape => "\x1B\x1B",
+        Key::PasteStart => "\x1B[200~",
+        Key::PasteEnd => "\x1B[201~",
+        Key::FocusIn => "\x1B[I",
+        Key::FocusOut => "\x1B[O",
+        Key::Mouse { x, y, button } => return format!("\x1B[<{};{};{}M", button, x, y).into_bytes(),
+        Key::Alt(c) => return format!("\x1B{}", c).into_bytes(),
+        Key::Char(c) => return c.to_string().into_bytes(),
+    };
+    sequence.as_bytes().to_vec()
+}
+
+/// Reads exactly one byte from `input`.
+fn read_byte<R: Read>(input: &mut R) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Most bytes taken from the input at once by `process_available_keys`.
+const INPUT_CHUNK: usize = 4096;
+
+/// Handles every key already waiting on `input` before the next redraw, so
+/// a burst such as a held arrow key costs one repaint instead of one per
+/// key. Waits as long as a single read does if nothing is waiting. Keys
+/// that read on, like a paste or a prompt, continue past the burst into
+/// `input`. Returns how many keys were handled.
+pub fn process_available_keys<R: Read>(state: &mut EditorState, input: &mut R) -> usize {
+    let mut chunk = [0; INPUT_CHUNK];
+    // An interrupted read, e.g. by a resize, just means no keys this time.
+    let len = input.read(&mut chunk).unwrap_or(0);
+    let mut pending = io::Cursor::new(&chunk[..len]).chain(input);
+
+    let mut handled = 0;
+    while !state.quit && (pending.get_ref().0.position() as usize) < len {
+        if process_keypress(state, &mut pending).is_none() {
+            break;
+        }
+        handled += 1;
+    }
+    handled
+}
+
+/// Reads one keypress from `input` and applies it to the editor state,
+/// adding it to the macro being recorded, if any. Returns the key that was
+/// handled, or `None` if no key arrived in time.
+pub fn process_keypress<R: Read>(state: &mut EditorState, input: &mut R) -> Option<Key> {
+    let key = editor_read_key(input).ok()??;
+
+    // The key goes ahead of any a prompt or paste it opens records. The key
+    // that starts recording isn't part of the macro, and the one that stops
+    // it has already ended it.
+    let focus = matches!(key, Key::FocusIn | Key::FocusOut);
+    let start = state.recording.as_ref().map(Vec::len).filter(|_| !focus);
+    handle_key(state, key, input);
+    if let (Some(at), Some(keys)) = (start, &mut state.recording) {
+        keys.insert(at, key);
+    }
+    Some(key)
+}
+
+/// Applies `key` to the editor state. Keys that read on, like a paste or a
+/// prompt, read the rest from `input`.
+pub fn handle_key<R: Read>(state: &mut EditorState, key: Key, input: &mut R) {
+    // Focus changes aren't keystrokes, so they leave confirmations and
+    // pending commands alone.
+    match key {
+        Key::FocusIn => return,
+        Key::FocusOut => {
+            state.focus_lost();
+            return;
+        }
+        _ => {}
+    }
+
+    // Quit confirmation only counts consecutive presses.
+    if key != Key::Char(ctrl_key(b'q')) {
+        state.quit_times = state.config.quit_times;
+    }
+    if key != Key::Char(ctrl_key(b'r')) {
+        state.reload_times = RELOAD_TIMES;
+    }
+    if key != Key::Char(ctrl_key(b's')) {
+        state.overwrite_times = OVERWRITE_TIMES;
+    }
+    if key != Key::Alt('q') {
+        state.close_times = CLOSE_TIMES;
+    }
+
+    // Enter on a line of build output opens the place it names.
+    if key == Key::Char(ENTER) && state.title == Some(BUILD_TITLE) {
+        state.open_location_at_cursor();
+        return;
+    }
+
+    if state.mode == Mode::Normal && process_normal_key(state, key, input) {
+        return;
+    }
+
+    match key {
+        Key::Char(c) if c == ctrl_key(b'q') => {
+            state.request_quit(); // Main loop exits, guard drops, mode restored
+        }
+        Key::Char(c) if c == ctrl_key(b's') => state.save_with_prompt(input),
+        Key::Char(c) if c == ctrl_key(b'f') => state.find(input),
+        // Ctrl-\, as in nano
+        Key::Char(c) if c == ctrl_key(b'\\') => state.replace(input),
+        Key::Char(c) if c == ctrl_key(b'o') => state.open_with_prompt(input),
+        Key::Char(c) if c == ctrl_key(b'p') => state.filter_with_prompt(input),
+        Key::Char(c) if c == ctrl_key(b'r') => state.reload(),
+        Key::Char(c) if c == ctrl_key(b'b') => state.jump_to_matching_bracket(),
+        Key::Char(c) if c == ctrl_key(b'e') => state.toggle_line_ending(),
+        Key::Char(c) if c == ctrl_key(b't') => state.toggle_split(),
+        Key::Char(c) if c == ctrl_key(b'u') => state.toggle_read_only(),
+        Key::Char(c) if c == ctrl_key(b'n') => state.switch_pane(),
+        Key::CtrlPageUp => state.prev_buffer(),
+        Key::CtrlPageDown => state.next_buffer(),
+        Key::Char(c) if c == ctrl_key(b'g') => state.goto_line_with_prompt(input),
+        Key::Char(c) if c == ctrl_key(b'z') => state.undo(),
+        Key::Char(c) if c == ctrl_key(b'y') => state.redo(),
+        Key::Char(c) if c == ctrl_key(b'k') => state.cut_line(),
+        Key::Char(c) if c == ctrl_key(b'c') => state.copy_line(),
+        Key::Char(c) if c == ctrl_key(b'v') => state.paste_line(),
+        Key::Char(c) if c == ctrl_key(b'd') => state.duplicate_line(),
+        // Ctrl-/ arrives as Ctrl-_.
+        Key::Char(c) if c == ctrl_key(b'_') => state.toggle_comment(),
+        // Ctrl-Space arrives as NUL.
+        Key::Char('\0') => state.toggle_selection(),
+        Key::Escape => {
+            state.mode = Mode::Normal;
+            state.selection_anchor = None;
+        }
+        Key::Char(c) if c == ctrl_key(b'l') => state.show_line_numbers = !state.show_line_numbers,
+        Key::Alt('l') => state.relative_line_numbers = !state.relative_line_numbers,
+        Key::Alt('w') => state.show_whitespace = !state.show_whitespace,
+        Key::Alt('z') => state.wrap_lines = !state.wrap_lines,
+        Key::Alt('x') => state.command_with_prompt(input),
+        Key::Alt('m') => state.build(),
+        Key::Alt('o') => state.toggle_build_output(),
+        Key::Alt('q') => state.close_buffer(),
+        Key::Char(ENTER) => state.insert_newline(),
+        Key::Char(BACKSPACE) => state.backspace(),
+        Key::Char(c) if c == ctrl_key(b'h') => state.backspace(),
+        Key::Char(c) if c == ctrl_key(b'w') => state.delete_word_backward(),
+        Key::Char('\t') if state.selection_anchor.is_some() => state.indent_selection(),
+        Key::Char('\t') => state.insert_tab(),
+        Key::BackTab => state.dedent_selection(),
+        Key::Delete => state.delete_char_forward(),
+        Key::Insert => state.overwrite = !state.overwrite,
+        Key::Char(c) if is_insertable(c) => state.type_char(c),
+        Key::ArrowUp | Key::ArrowDown | Key::ArrowLeft | Key::ArrowRight => {
+            state.move_cursor(key);
+        }
+        Key::PageUp | Key::PageDown => state.move_page(key),
+        Key::Home => state.smart_home(),
+        Key::End => state.move_to_line_end(),
+        Key::PasteStart => {
+            let text = read_paste(input);
+            state.record_keys(text.chars().map(Key::Char).chain([Key::PasteEnd]));
+            state.paste_text(&text);
+        }
+        Key::CtrlArrowRight => state.move_word_right(),
+        Key::CtrlArrowLeft => state.move_word_left(),
+        Key::AltArrowUp => state.move_line_up(),
+        Key::AltArrowDown => state.move_line_down(),
+        Key::Mouse { x, y, button: MOUSE_LEFT } => state.click(x, y),
+        Key::Mouse { button: MOUSE_WHEEL_UP, .. } => state.scroll_view(-WHEEL_LINES),
+        Key::Mouse { button: MOUSE_WHEEL_DOWN, .. } => state.scroll_view(WHEEL_LINES),
+        _ => {}
+    }
+
+    // Uncomment the following lines to simulate a panic:
+    // if key == Key::Char('p') {
+    //     panic!("Simulating a panic to test the Drop guard!");
+    // }
+}
+
+/// Handles the keys that mean something else in Normal mode, where typing
+/// never inserts text, including the second key of a pending operator.
+/// Returns false for keys that work the same in both modes, such as the
+/// Ctrl shortcuts.
+fn process_normal_key<R: Read>(state: &mut EditorState, key: Key, input: &mut R) -> bool {
+    if let Some(operator) = state.take_pending_operator() {
+        state.pending_count = None;
+        match key {
+            Key::Escape => {}
+            Key::Char(c) => state.apply_operator(operator, c),
+            _ => return false,
+        }
+        return true;
+    }
+
+    let Key::Char(c) = key else {
+        state.pending_count = None;
+        return false;
+    };
+    // A leading 0 is the motion to column 0 rather than part of a count.
+    if let Some(digit) = c.to_digit(10)
+        && (digit != 0 || state.pending_count.is_some())
+    {
+        state.push_count_digit(digit);
+        return true;
+    }
+    if let Some(operator) = Operator::from_key(c) {
+        state.pending_count = None;
+        state.pending_operator = Some((operator, Instant::now()));
+        return true;
+    }
+
+    let count = state.pending_count.take().unwrap_or(1);
+    let repeat = |state: &mut EditorState, command: fn(&mut EditorState)| {
+        for _ in 0..count {
+            command(state);
+        }
+    };
+    match c {
+        'h' | BACKSPACE => repeat(state, |state| state.move_cursor(Key::ArrowLeft)),
+        'j' | ENTER => repeat(state, |state| state.move_cursor(Key::ArrowDown)),
+        'k' => repeat(state, |state| state.move_cursor(Key::ArrowUp)),
+        'l' | ' ' => repeat(state, |state| state.move_cursor(Key::ArrowRight)),
+        '0' => state.cursor_x = 0,
+        'i' => state.mode = Mode::Insert,
+        'x' => repeat(state, EditorState::delete_char_forward),
+        'o' => state.open_line_below(),
+        'p' => repeat(state, EditorState::paste_line),
+        'q' => state.toggle_recording(),
+        '@' => replay_macro(state, count, input),
+        ':' => state.command_with_prompt(input),
+        // As in vi; Ctrl-I arrives as Tab.
+        c if c == ctrl_key(b'o') => repeat(state, EditorState::jump_back),
+        '\t' => repeat(state, EditorState::jump_forward),
+        c if is_insertable(c) => {}
+        _ => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod input_tests {
+    use super::*;
+    use crate::torus::editor::QUIT_TIMES;
+    use crate::torus::mode::OPERATOR_TIMEOUT;
+    use std::io::Cursor;
+
+    /// Returns a reader standing in for stdin, preloaded with `bytes`.
+    /// Reads past `bytes` see EOF, like a closed pipe.
+    fn input_with(bytes: &[u8]) -> Cursor<Vec<u8>> {
+        Cursor::new(bytes.to_vec())
+    }
+
+    #[test]
+    fn test_available_keys_are_handled_in_one_pass() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string(); 10];
+        let mut input = input_with(b"jj\x1B[Bl\x1B[C");
+
+        assert_eq!(process_available_keys(&mut state, &mut input), 5);
+        assert_eq!((state.cursor_x, state.cursor_y), (2, 3));
+        assert_eq!(process_available_keys(&mut state, &mut input), 0);
+    }
+
+    #[test]
+    fn test_available_keys_stop_at_quit() {
+        let mut state = EditorState::with_size(24, 80);
+        let mut input = input_with(b"\x11jj");
+
+        assert_eq!(process_available_keys(&mut state, &mut input), 1);
+        assert!(state.quit);
+    }
+
+    #[test]
+    fn test_read_key_returns_bytes_in_order() {
+        let mut input = input_with(b"ab");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Char('a')));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Char('b')));
+        assert_eq!(editor_read_key(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_quit_key_sets_quit_flag() {
+        let mut state = EditorState::with_size(24, 80);
+        let mut input = input_with(&[ctrl_key(b'q') as u8]);
+
+        assert_eq!(process_keypress(&mut state, &mut input), Some(Key::Char('\x11')));
+        assert!(state.quit);
+    }
+
+    #[test]
+    fn test_read_key_parses_arrow_up() {
+        let mut input = input_with(b"\x1B[A");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::ArrowUp));
+    }
+
+    #[test]
+    fn test_read_key_parses_all_arrows() {
+        let mut input = input_with(b"\x1B[B\x1B[C\x1B[D");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::ArrowDown));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::ArrowRight));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::ArrowLeft));
+    }
+
+    #[test]
+    fn test_read_key_lone_escape() {
+        let mut input = input_with(b"\x1B");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Escape));
+    }
+
+    #[test]
+    fn test_read_key_parses_page_up_delete_and_insert() {
+        let mut input = input_with(b"\x1B[5~\x1B[3~\x1B[2~");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::PageUp));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Delete));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Insert));
+    }
+
+    #[test]
+    fn test_read_key_parses_home_and_end_variants() {
+        let mut input = input_with(b"\x1B[H\x1B[1~\x1B[7~\x1B[F\x1B[4~\x1B[8~\x1B[6~");
+
+        for expected in [Key::Home, Key::Home, Key::Home, Key::End, Key::End, Key::End] {
+            assert_eq!(editor_read_key(&mut input).unwrap(), Some(expected));
+        }
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::PageDown));
+    }
+
+    #[test]
+    fn test_read_key_partial_sequence_is_escape() {
+        let mut input = input_with(b"\x1B[5");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Escape));
+    }
+
+    #[test]
+    fn test_ctrl_key() {
+        assert_eq!(ctrl_key(b's'), '\x13');
+        assert_eq!(ctrl_key(b'q'), '\x11');
+    }
+
+    #[test]
+    fn test_printable_keys_are_inserted() {
+        let mut state = EditorState::with_size(24, 80);
+        let mut input = input_with(b"iq\tx");
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.rows, vec!["q\tx"]);
+        assert!(!state.quit);
+    }
+
+    #[test]
+    fn test_normal_mode_keys_move_without_inserting() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string(), "def".to_string()];
+        let mut input = input_with(b"lljhkzq");
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.mode, Mode::Normal);
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 0));
+        assert_eq!(state.rows, vec!["abc", "def"]);
+        assert!(!state.dirty);
+    }
+
+    #[test]
+    fn test_insert_and_escape_switch_modes() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+        let mut input = input_with(b"lxiX\x1B");
+
+        process_keypress(&mut state, &mut input);
+        process_keypress(&mut state, &mut input);
+        assert_eq!(state.rows, vec!["ac"]);
+
+        process_keypress(&mut state, &mut input);
+        assert_eq!(state.mode, Mode::Insert);
+        process_keypress(&mut state, &mut input);
+        assert_eq!(state.rows, vec!["aXc"]);
+
+        process_keypress(&mut state, &mut input);
+        assert_eq!(state.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn test_o_opens_indented_line_below_in_insert_mode() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["  abc".to_string(), "def".to_string()];
+        let mut input = input_with(b"oxy");
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.mode, Mode::Insert);
+        assert_eq!(state.rows, vec!["  abc", "  xy", "def"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (4, 1));
+    }
+
+    #[test]
+    fn test_dd_then_p_restores_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        state.cursor_y = 1;
+
+        let mut input = input_with(b"dd");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.rows, vec!["a", "c"]);
+        assert_eq!(state.clipboard, "b");
+
+        let mut input = input_with(b"kp");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.rows, vec!["a", "b", "c"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+    }
+
+    #[test]
+    fn test_yy_then_p_duplicates_line() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a".to_string(), "b".to_string()];
+        let mut input = input_with(b"yyp");
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.rows, vec!["a", "a", "b"]);
+        assert_eq!(state.cursor_y, 1);
+    }
+
+    #[test]
+    fn test_z_commands_scroll_without_moving_cursor() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["x".to_string(); 100];
+        state.cursor_y = 49;
+
+        let mut input = input_with(b"zt");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.row_offset, 49);
+
+        let mut input = input_with(b"zz");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.row_offset, 37);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 49));
+    }
+
+    #[test]
+    fn test_escape_or_timeout_cancels_pending_operator() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "c".to_string()];
+
+        // Each key is read separately, since ESC x would be Alt-X.
+        for key in [b"d", b"\x1B", b"x"] {
+            process_keypress(&mut state, &mut input_with(key));
+        }
+        assert_eq!(state.rows, vec!["b", "c"]);
+
+        let stale = Instant::now().checked_sub(OPERATOR_TIMEOUT).unwrap();
+        state.pending_operator = Some((Operator::Delete, stale));
+        let mut input = input_with(b"d");
+        process_keypress(&mut state, &mut input);
+        assert_eq!(state.rows, vec!["b", "c"]);
+        assert!(state.pending_operator.is_some());
+    }
+
+    #[test]
+    fn test_count_repeats_motion() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["x".to_string(); 20];
+
+        let mut input = input_with(b"5j");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.cursor_y, 5);
+        assert_eq!(state.pending_count, None);
+
+        let mut input = input_with(b"12j");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.cursor_y, 17);
+
+        // A count past the end stops at the end of the buffer.
+        let mut input = input_with(b"5j");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.cursor_y, 20);
+    }
+
+    #[test]
+    fn test_count_repeats_delete_and_leading_zero_is_motion() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abcdef".to_string()];
+        let mut input = input_with(b"ll3x0");
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.rows, vec!["abf"]);
+        assert_eq!(state.cursor_x, 0);
+    }
+
+    #[test]
+    fn test_backspace_key_deletes() {
+        let mut state = EditorState::with_size(24, 80);
+        let mut input = input_with(b"iab\x7F");
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.rows, vec!["a"]);
+    }
+
+    #[test]
+    fn test_intervening_key_resets_quit_confirmation() {
+        let mut state = EditorState::with_size(24, 80);
+        let quit = ctrl_key(b'q') as u8;
+        let mut input = input_with(&[b'i', b'x', quit, quit, b'y', quit]);
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert!(!state.quit);
+        assert_eq!(state.quit_times, QUIT_TIMES - 1);
+    }
+
+    #[test]
+    fn test_read_key_decodes_multibyte_utf8() {
+        let mut input = input_with("é😀".as_bytes());
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Char('é')));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Char('😀')));
+    }
+
+    #[test]
+    fn test_read_key_invalid_utf8_is_replacement_char() {
+        let mut input = input_with(&[0xFF, 0xC3, b'a', 0xC3]);
+
+        // An invalid leading byte, a bad continuation byte and a truncated sequence.
+        for _ in 0..3 {
+            assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Char(char::REPLACEMENT_CHARACTER)));
+        }
+    }
+
+    #[test]
+    fn test_multibyte_keys_are_inserted() {
+        let mut state = EditorState::with_size(24, 80);
+        let mut input = input_with("iné".as_bytes());
+
+        while process_keypress(&mut state, &mut input).is_some() {}
+
+        assert_eq!(state.rows, vec!["né"]);
+    }
+
+    #[test]
+    fn test_read_key_parses_sgr_mouse_click() {
+        let mut input = input_with(b"\x1B[<0;12;5M\x1B[<0;12;5m");
+
+        let click = Key::Mouse { x: 12, y: 5, button: MOUSE_LEFT };
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(click));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(click));
+    }
+
+    #[test]
+    fn test_read_key_malformed_mouse_is_escape() {
+        let mut input = input_with(b"\x1B[<0;12M");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Escape));
+    }
+
+    #[test]
+    fn test_left_click_moves_cursor() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["first".to_string(), "second".to_string()];
+        let mut input = input_with(b"\x1B[<0;4;2M");
+
+        process_keypress(&mut state, &mut input);
+
+        assert_eq!((state.cursor_x, state.cursor_y), (3, 1));
+        assert!(state.rows.iter().all(|row| !row.contains('<')));
+    }
+
+    #[test]
+    fn test_wheel_scrolls_view_without_moving_cursor() {
+        let mut state = EditorState::with_size(10, 80);
+        state.rows = vec!["x".to_string(); 50];
+        state.cursor_y = 20;
+        state.row_offset = 15;
+        let mut input = input_with(b"\x1B[<65;1;1M");
+
+        process_keypress(&mut state, &mut input);
+        assert_eq!(state.row_offset, 18);
+
+        let mut input = input_with(b"\x1B[<64;1;1M\x1B[<64;1;1M");
+        while process_keypress(&mut state, &mut input).is_some() {}
+        assert_eq!(state.row_offset, 12);
+
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 20));
+        assert_eq!(state.rows, vec!["x"; 50]);
+    }
+
+    #[test]
+    fn test_goto_line_rejects_non_numeric_input() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["x".to_string(); 10];
+        state.cursor_y = 4;
+        let mut input = input_with(b"\x07abc\r");
+
+        process_keypress(&mut state, &mut input);
+
+        assert_eq!(state.cursor_y, 4);
+        assert_eq!(state.status_msg, "Not a line number: abc");
+    }
+
+    #[test]
+    fn test_read_key_parses_alt_arrows() {
+        let mut input = input_with(b"\x1B[1;3A\x1B[1;3B");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::AltArrowUp));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::AltArrowDown));
+    }
+
+    #[test]
+    fn test_tab_indents_selection_and_shift_tab_dedents() {
+        let mut state = EditorState::with_size(24, 80);
+        state.mode = Mode::Insert;
+        state.rows = vec!["a".to_string(), "b".to_string()];
+        state.selection_anchor = Some((0, 0));
+        (state.cursor_y, state.cursor_x) = (1, 1);
+
+        process_keypress(&mut state, &mut input_with(b"\t"));
+        assert_eq!(state.rows, vec!["\ta", "\tb"]);
+
+        process_keypress(&mut state, &mut input_with(b"\x1B[Z"));
+        assert_eq!(state.rows, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_read_key_parses_ctrl_arrows() {
+        let mut input = input_with(b"\x1B[1;5C\x1B[1;5D\x1B[1;2C");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::CtrlArrowRight));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::CtrlArrowLeft));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Escape));
+    }
+
+    #[test]
+    fn test_bracketed_paste_is_inserted_verbatim() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["    x".to_string()];
+        state.cursor_x = 5;
+        let mut input = input_with(b"\x1B[200~a\tb\r\nc\x11\x1B[201~");
+
+        process_keypress(&mut state, &mut input);
+
+        assert_eq!(state.rows, vec!["    xa\tb", "c\x11"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (2, 1));
+        assert!(!state.quit);
+    }
+
+    #[test]
+    fn test_read_key_parses_ctrl_page_keys() {
+        let mut input = input_with(b"\x1B[5;5~\x1B[6;5~");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::CtrlPageUp));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::CtrlPageDown));
+    }
+
+    #[test]
+    fn test_read_key_parses_alt_letters() {
+        let mut input = input_with(b"\x1Bc\x1B7\x1B ");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Alt('c')));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Alt('7')));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::Escape));
+    }
+
+    #[test]
+    fn test_key_bytes_decode_to_the_same_key() {
+        let keys = [
+            Key::ArrowUp,
+            Key::CtrlArrowLeft,
+            Key::AltArrowDown,
+            Key::BackTab,
+            Key::CtrlPageDown,
+            Key::Home,
+            Key::Delete,
+            Key::PasteStart,
+            Key::PasteEnd,
+            Key::FocusOut,
+            Key::Mouse { x: 12, y: 5, button: MOUSE_LEFT },
+            Key::Alt('x'),
+            Key::Char('é'),
+            Key::Escape,
+            Key::Char('j'),
+        ];
+        let bytes: Vec<u8> = keys.iter().flat_map(|&key| key_bytes(key)).collect();
+        let mut input = input_with(&bytes);
+
+        for key in keys {
+            assert_eq!(editor_read_key(&mut input).unwrap(), Some(key));
+        }
+    }
+
+    #[test]
+    fn test_read_key_parses_focus_events() {
+        let mut input = input_with(b"\x1B[I\x1B[O");
+
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::FocusIn));
+        assert_eq!(editor_read_key(&mut input).unwrap(), Some(Key::FocusOut));
+    }
+}
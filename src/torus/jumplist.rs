@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+
+use super::editor::EditorState;
+
+/// Most positions a jump list remembers; the oldest are dropped first.
+const MAX_JUMPS: usize = 100;
+
+/// Cursor positions left by big jumps, as `(row, col)` pairs, with a
+/// place in them that going back and forward moves.
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: VecDeque<(usize, usize)>,
+    /// Index of the entry last stepped to; equal to the length when not
+    /// travelling through the list.
+    index: usize,
+}
+
+impl JumpList {
+    /// Records `pos` as the place a jump left from, dropping any positions
+    /// ahead of the current place.
+    pub fn push(&mut self, pos: (usize, usize)) {
+        self.jumps.truncate(self.index + 1);
+        if self.jumps.back() != Some(&pos) {
+            self.push_back(pos);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// Steps back to the previous position. Leaving the newest end first
+    /// records `current`, so going forward can return to it.
+    pub fn back(&mut self, current: (usize, usize)) -> Option<(usize, usize)> {
+        if self.index == self.jumps.len() && self.jumps.back() != Some(&current) {
+            self.push_back(current);
+        }
+        self.index = self.index.min(self.jumps.len() - 1).checked_sub(1)?;
+        Some(self.jumps[self.index])
+    }
+
+    /// Steps forward to the position that going back left.
+    pub fn forward(&mut self) -> Option<(usize, usize)> {
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.jumps[self.index])
+    }
+
+    fn push_back(&mut self, pos: (usize, usize)) {
+        self.jumps.push_back(pos);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.pop_front();
+        }
+    }
+}
+
+impl EditorState {
+    /// Remembers the cursor position before a big jump moves it.
+    pub fn record_jump(&mut self) {
+        let pos = (self.cursor_y, self.cursor_x);
+        self.jumps.push(pos);
+    }
+
+    /// Returns to the position before the last jump.
+    pub fn jump_back(&mut self) {
+        let current = (self.cursor_y, self.cursor_x);
+        match self.jumps.back(current) {
+            Some(pos) => self.move_to_jump(pos),
+            None => self.ring_bell(),
+        }
+    }
+
+    /// Redoes a jump undone by `jump_back`.
+    pub fn jump_forward(&mut self) {
+        match self.jumps.forward() {
+            Some(pos) => self.move_to_jump(pos),
+            None => self.ring_bell(),
+        }
+    }
+
+    /// Moves the cursor to a remembered position. Edits since it was
+    /// recorded may have shortened the buffer, so it is clamped rather than
+    /// tracked through them.
+    fn move_to_jump(&mut self, (y, x): (usize, usize)) {
+        self.cursor_y = y.min(self.num_rows().saturating_sub(1));
+        self.cursor_x = x.min(self.row_len(self.cursor_y));
+    }
+}
+
+#[cfg(test)]
+mod jumplist_tests {
+    use super::*;
+    use crate::torus::input_handler::Key;
+
+    #[test]
+    fn test_back_and_forward_walk_the_list() {
+        let mut jumps = JumpList::default();
+        jumps.push((1, 0));
+        jumps.push((5, 2));
+
+        assert_eq!(jumps.back((9, 0)), Some((5, 2)));
+        assert_eq!(jumps.back((5, 2)), Some((1, 0)));
+        assert_eq!(jumps.back((1, 0)), None);
+        assert_eq!(jumps.forward(), Some((5, 2)));
+        assert_eq!(jumps.forward(), Some((9, 0)));
+        assert_eq!(jumps.forward(), None);
+    }
+
+    #[test]
+    fn test_push_drops_forward_history() {
+        let mut jumps = JumpList::default();
+        jumps.push((1, 0));
+        jumps.push((2, 0));
+        jumps.back((3, 0));
+        jumps.back((2, 0));
+
+        jumps.push((7, 0));
+
+        assert_eq!(jumps.forward(), None);
+        assert_eq!(jumps.back((8, 0)), Some((7, 0)));
+        assert_eq!(jumps.back((7, 0)), Some((1, 0)));
+    }
+
+    #[test]
+    fn test_oldest_jumps_are_dropped() {
+        let mut jumps = JumpList::default();
+        for row in 0..MAX_JUMPS + 10 {
+            jumps.push((row, 0));
+        }
+
+        let newest = (MAX_JUMPS + 9, 0);
+        let mut last = None;
+        while let Some(pos) = jumps.back(newest) {
+            last = Some(pos);
+        }
+        assert_eq!(last, Some((10, 0)));
+    }
+
+    #[test]
+    fn test_jump_back_clamps_to_shorter_buffer() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abcdef".to_string(); 10];
+        state.cursor_y = 8;
+        state.cursor_x = 5;
+        state.record_jump();
+        state.goto_line(1);
+        state.rows = vec!["abc".to_string(); 3];
+
+        state.jump_back();
+
+        assert_eq!((state.cursor_y, state.cursor_x), (2, 3));
+    }
+
+    #[test]
+    fn test_page_down_can_be_jumped_back() {
+        let mut state = EditorState::with_size(10, 80);
+        state.rows = vec!["x".to_string(); 50];
+        state.cursor_y = 3;
+
+        state.move_page(Key::PageDown);
+        assert_eq!(state.cursor_y, 13);
+
+        state.jump_back();
+        assert_eq!(state.cursor_y, 3);
+        state.jump_forward();
+        assert_eq!(state.cursor_y, 13);
+    }
+}
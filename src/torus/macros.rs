@@ -0,0 +1,128 @@
+use std::io::{Cursor, Read};
+
+use super::editor::EditorState;
+use super::input_handler::{key_bytes, process_keypress, Key};
+
+impl EditorState {
+    /// Starts recording keys into a macro, or stops and keeps what was
+    /// recorded as the macro `@` replays.
+    pub fn toggle_recording(&mut self) {
+        match self.recording.take() {
+            Some(keys) => {
+                self.set_status_message(format!("Recorded {} keys", keys.len()));
+                self.last_macro = keys;
+            }
+            None => {
+                self.recording = Some(Vec::new());
+                self.set_status_message("Recording macro; q to stop".to_string());
+            }
+        }
+    }
+
+    /// Adds `keys` to the macro being recorded, if any: the keys typed
+    /// into a prompt, or the text of a paste.
+    pub fn record_keys(&mut self, keys: impl IntoIterator<Item = Key>) {
+        if let Some(recorded) = &mut self.recording {
+            recorded.extend(keys);
+        }
+    }
+}
+
+/// Replays the last recorded macro `count` times through the same key
+/// handling as typed keys, reading them back from the bytes the terminal
+/// would have sent, so prompts and pastes get their recorded text too. A
+/// prompt the macro leaves open carries on reading from `input`. Rings the
+/// bell while recording or replaying, since the macro would then contain
+/// itself.
+pub fn replay_macro<R: Read>(state: &mut EditorState, count: usize, input: &mut R) {
+    if state.recording.is_some() || state.replaying || state.last_macro.is_empty() {
+        state.ring_bell();
+        return;
+    }
+
+    let bytes: Vec<u8> = state.last_macro.iter().flat_map(|&key| key_bytes(key)).collect();
+    // A trait object keeps the reader's type the same however deep the key
+    // handling nests, which stops it being instantiated forever.
+    let input: &mut dyn Read = input;
+    state.replaying = true;
+    for _ in 0..count {
+        let mut keys = Cursor::new(&bytes[..]).chain(&mut *input);
+        while !state.quit && (keys.get_ref().0.position() as usize) < bytes.len() {
+            if process_keypress(state, &mut keys).is_none() {
+                break;
+            }
+        }
+    }
+    state.replaying = false;
+}
+
+#[cfg(test)]
+mod macros_tests {
+    use super::*;
+    use crate::torus::input_handler::process_keypress;
+    use std::io::Cursor;
+
+    fn type_keys(state: &mut EditorState, bytes: &[u8]) {
+        let mut input = Cursor::new(bytes.to_vec());
+        while process_keypress(state, &mut input).is_some() {}
+    }
+
+    #[test]
+    fn test_replay_recorded_insert() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["x".to_string()];
+
+        type_keys(&mut state, b"qiab");
+        type_keys(&mut state, b"\x1B");
+        type_keys(&mut state, b"q");
+        assert_eq!(state.rows, vec!["abx"]);
+        assert_eq!(state.recording, None);
+
+        type_keys(&mut state, b"@");
+        assert_eq!(state.rows, vec!["ababx"]);
+
+        type_keys(&mut state, b"2@");
+        assert_eq!(state.rows, vec!["ababababx"]);
+    }
+
+    #[test]
+    fn test_replay_composes_with_commands() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+        type_keys(&mut state, b"qxjq");
+        type_keys(&mut state, b"@");
+
+        assert_eq!(state.rows, vec!["", "", "c"]);
+        assert_eq!(state.cursor_y, 2);
+    }
+
+    #[test]
+    fn test_replay_includes_prompt_and_paste_text() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a".to_string()];
+
+        type_keys(&mut state, b"q:set nu\r");
+        type_keys(&mut state, b"\x1B[200~xy\x1B[201~q");
+        assert_eq!(state.rows, vec!["xya"]);
+        assert!(state.show_line_numbers);
+
+        state.show_line_numbers = false;
+        type_keys(&mut state, b"@");
+
+        assert_eq!(state.rows, vec!["xyxya"]);
+        assert!(state.show_line_numbers);
+    }
+
+    #[test]
+    fn test_replay_while_recording_rings_bell() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["abc".to_string()];
+        type_keys(&mut state, b"qxq");
+
+        type_keys(&mut state, b"q@");
+
+        assert!(state.bell_pending);
+        assert_eq!(state.rows, vec!["bc"]);
+    }
+}
@@ -1 +1,34 @@
+pub mod autosave;
+pub mod bracket;
+pub mod build;
+pub mod buffer;
+pub mod command;
+pub mod comment;
+pub mod config;
+pub mod editor;
+pub mod error;
+pub mod filter;
+pub mod highlight;
+pub mod indent;
+pub mod input_handler;
+pub mod jumplist;
+pub mod macros;
+pub mod mode;
+pub mod motion;
+pub mod platform;
+pub mod positions;
+pub mod prompt;
+pub mod render;
+pub mod ruler;
+pub mod scrollbar;
+pub mod search;
+pub mod selection;
+pub mod stats;
+pub mod statusline;
+pub mod swap;
+pub mod tabbar;
 pub mod terminal_handler;
+pub mod theme;
+pub mod undo;
+pub mod whitespace;
+pub mod wrap;
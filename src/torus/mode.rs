@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use super::editor::EditorState;
+
+/// How long a pending operator waits for its second key.
+pub const OPERATOR_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Largest repeat count, so a long run of digits can't stall the editor.
+const MAX_COUNT: usize = 9999;
+
+/// Which keys type text: in Normal mode letters are commands, in Insert
+/// mode they are inserted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+}
+
+impl Mode {
+    /// Name shown in the status bar.
+    pub fn label(self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+        }
+    }
+}
+
+/// First key of a two-key Normal-mode command such as `dd` or `zz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Yank,
+    /// `z`, which scrolls the cursor line to a place on the screen.
+    Scroll,
+}
+
+impl Operator {
+    /// The operator typed with `c`, if any.
+    pub fn from_key(c: char) -> Option<Operator> {
+        match c {
+            'd' => Some(Operator::Delete),
+            'y' => Some(Operator::Yank),
+            'z' => Some(Operator::Scroll),
+            _ => None,
+        }
+    }
+}
+
+impl EditorState {
+    /// Appends `digit` to the repeat count being typed.
+    pub fn push_count_digit(&mut self, digit: u32) {
+        let count = self.pending_count.unwrap_or(0) * 10 + digit as usize;
+        self.pending_count = Some(count.min(MAX_COUNT));
+    }
+
+    /// Takes the pending operator, unless it has timed out.
+    pub fn take_pending_operator(&mut self) -> Option<Operator> {
+        self.pending_operator
+            .take()
+            .filter(|(_, since)| since.elapsed() < OPERATOR_TIMEOUT)
+            .map(|(operator, _)| operator)
+    }
+
+    /// Finishes the pending `operator` with the second key `c`: `dd` and
+    /// `yy` cut or copy the cursor line, while `zz`, `zt` and `zb` scroll it
+    /// to the center, top or bottom of the screen. Any other key cancels
+    /// the operator.
+    pub fn apply_operator(&mut self, operator: Operator, c: char) {
+        match (operator, c) {
+            (Operator::Delete, 'd') => self.cut_line(),
+            (Operator::Yank, 'y') => self.yank_line(),
+            (Operator::Scroll, 'z') => self.scroll_cursor_to_center(),
+            (Operator::Scroll, 't') => self.scroll_cursor_to_top(),
+            (Operator::Scroll, 'b') => self.scroll_cursor_to_bottom(),
+            _ => self.ring_bell(),
+        }
+    }
+
+    /// Opens an empty line below the cursor row, indented like it, and
+    /// switches to Insert mode on it.
+    pub fn open_line_below(&mut self) {
+        self.cursor_x = self.row_len(self.cursor_y);
+        self.insert_newline();
+        self.mode = Mode::Insert;
+    }
+}
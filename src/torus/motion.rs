@@ -0,0 +1,214 @@
+use super::editor::EditorState;
+use super::undo::EditOp;
+
+/// Kinds of character that word motion treats as separate runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Space,
+    Word,
+    Punct,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+/// Column of the next word start after `x` in `row`: skips the run of
+/// characters under the cursor, then any whitespace. Returns the row length
+/// if no word follows.
+pub fn next_word_start(row: &str, x: usize) -> usize {
+    let chars: Vec<char> = row.chars().collect();
+    let mut x = x.min(chars.len());
+
+    if let Some(&c) = chars.get(x) {
+        let class = char_class(c);
+        while x < chars.len() && char_class(chars[x]) == class {
+            x += 1;
+        }
+    }
+    while x < chars.len() && char_class(chars[x]) == CharClass::Space {
+        x += 1;
+    }
+    x
+}
+
+/// Column of the start of the word before `x` in `row`: skips whitespace
+/// backwards, then the run of characters before it. Returns 0 if no word
+/// precedes `x`.
+pub fn prev_word_start(row: &str, x: usize) -> usize {
+    let chars: Vec<char> = row.chars().collect();
+    let mut x = x.min(chars.len());
+
+    while x > 0 && char_class(chars[x - 1]) == CharClass::Space {
+        x -= 1;
+    }
+    if x > 0 {
+        let class = char_class(chars[x - 1]);
+        while x > 0 && char_class(chars[x - 1]) == class {
+            x -= 1;
+        }
+    }
+    x
+}
+
+impl EditorState {
+    /// Moves the cursor to the start of the next word, continuing onto the
+    /// following line from the end of a line.
+    pub fn move_word_right(&mut self) {
+        let Some(row) = self.row(self.cursor_y) else {
+            return;
+        };
+
+        if self.cursor_x >= row.chars().count() {
+            if self.cursor_y + 1 < self.num_rows() {
+                self.cursor_y += 1;
+                self.cursor_x = 0;
+            }
+        } else {
+            self.cursor_x = next_word_start(row, self.cursor_x);
+        }
+    }
+
+    /// Moves the cursor to the start of the previous word, continuing onto
+    /// the end of the previous line from the start of a line.
+    pub fn move_word_left(&mut self) {
+        if self.cursor_x == 0 {
+            if self.cursor_y > 0 {
+                self.cursor_y -= 1;
+                self.cursor_x = self.row_len(self.cursor_y);
+            }
+        } else if let Some(row) = self.row(self.cursor_y) {
+            self.cursor_x = prev_word_start(row, self.cursor_x);
+        }
+    }
+
+    /// Moves the cursor to the first non-blank character of the line, or
+    /// to column 0 if it is already there.
+    pub fn smart_home(&mut self) {
+        let indent = self
+            .row(self.cursor_y)
+            .map_or(0, |row| row.chars().take_while(|c| c.is_whitespace()).count());
+        self.cursor_x = if self.cursor_x == indent { 0 } else { indent };
+    }
+
+    /// Moves the cursor to the end of the line.
+    pub fn move_to_line_end(&mut self) {
+        self.cursor_x = self.row_len(self.cursor_y);
+    }
+
+    /// Deletes back to the start of the previous word, including whitespace
+    /// between it and the cursor, as one undo unit. At the start of a line
+    /// it joins the line onto the previous one, like Backspace.
+    pub fn delete_word_backward(&mut self) {
+        let y = self.cursor_y;
+        let x = self.cursor_x.min(self.row_len(y));
+        let Some(row) = self.row(y) else {
+            return;
+        };
+        if x == 0 {
+            self.delete_char();
+            return;
+        }
+
+        let from = prev_word_start(row, x);
+        // Delete right to left so each column is still valid when applied.
+        let chars: Vec<char> = row.chars().collect();
+        let ops = (from..x)
+            .rev()
+            .map(|i| EditOp::DeleteChar { at: (y, i), c: chars[i] })
+            .collect();
+        self.edit_all(ops, (y, from));
+    }
+}
+
+#[cfg(test)]
+mod motion_tests {
+    use super::*;
+
+    #[test]
+    fn test_next_word_start_stops() {
+        let row = "foo  bar.baz";
+        let stops: Vec<usize> = [0, 5, 8, 9].iter().map(|&x| next_word_start(row, x)).collect();
+
+        assert_eq!(stops, vec![5, 8, 9, 12]);
+        assert_eq!(next_word_start(row, 3), 5);
+        assert_eq!(next_word_start(row, 12), 12);
+    }
+
+    #[test]
+    fn test_prev_word_start_stops() {
+        let row = "foo  bar.baz";
+        let stops: Vec<usize> = [12, 9, 8, 5].iter().map(|&x| prev_word_start(row, x)).collect();
+
+        assert_eq!(stops, vec![9, 8, 5, 0]);
+        assert_eq!(prev_word_start(row, 4), 0);
+        assert_eq!(prev_word_start(row, 0), 0);
+    }
+
+    #[test]
+    fn test_word_motion_wraps_lines() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab cd".to_string(), "ef".to_string()];
+        state.cursor_x = 3;
+
+        state.move_word_right();
+        assert_eq!((state.cursor_x, state.cursor_y), (5, 0));
+        state.move_word_right();
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+
+        state.move_word_left();
+        assert_eq!((state.cursor_x, state.cursor_y), (5, 0));
+        state.move_word_left();
+        assert_eq!((state.cursor_x, state.cursor_y), (3, 0));
+    }
+
+    #[test]
+    fn test_smart_home_toggles_between_indent_and_column_zero() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["    foo".to_string()];
+        state.cursor_x = 6;
+
+        state.smart_home();
+        assert_eq!(state.cursor_x, 4);
+        state.smart_home();
+        assert_eq!(state.cursor_x, 0);
+        state.smart_home();
+        assert_eq!(state.cursor_x, 4);
+
+        state.move_to_line_end();
+        assert_eq!(state.cursor_x, 7);
+    }
+
+    #[test]
+    fn test_delete_word_backward() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["hello world".to_string()];
+        state.cursor_x = 11;
+
+        state.delete_word_backward();
+        assert_eq!(state.rows, vec!["hello "]);
+        assert_eq!(state.cursor_x, 6);
+
+        state.undo();
+        assert_eq!(state.rows, vec!["hello world"]);
+        assert_eq!(state.cursor_x, 11);
+    }
+
+    #[test]
+    fn test_delete_word_at_line_start_joins_lines() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.cursor_y = 1;
+
+        state.delete_word_backward();
+
+        assert_eq!(state.rows, vec!["abcd"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (2, 0));
+    }
+}
@@ -0,0 +1,18 @@
+// Platform-specific terminal access. Each platform module provides the same
+// small surface: `RawMode`, `input_reader`, `window_size`, `write_stdout`,
+// `install_resize_handler` and `process_alive`; everything above it is shared.
+
+use std::sync::atomic::AtomicBool;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub use unix::*;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+pub use windows::*;
+
+/// Set when the terminal is resized and cleared once the main loop has seen it.
+pub static WINDOW_RESIZED: AtomicBool = AtomicBool::new(false);
@@ -0,0 +1,151 @@
+use libc::{
+    c_void, tcgetattr, tcsetattr, termios as Termios, ECHO, ICANON, ICRNL, IEXTEN, ISIG, IXON,
+    STDOUT_FILENO, TCSANOW, VMIN, VTIME,
+};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::sync::atomic::Ordering;
+use std::{mem, ptr};
+
+use super::WINDOW_RESIZED;
+
+/// The terminal settings in place before raw mode was enabled.
+pub struct RawMode {
+    original_termios: Termios,
+}
+
+impl RawMode {
+    /// Switches stdin to raw mode, returning the settings to restore later.
+    pub fn enable() -> io::Result<Self> {
+        let fd = io::stdin().as_raw_fd();
+        let mut original_termios: Termios = unsafe { mem::zeroed() };
+
+        // Get the current terminal attributes
+        if unsafe { tcgetattr(fd, &mut original_termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let raw_termios = raw_termios(&original_termios);
+
+        // Set the new terminal attributes immediately
+        if unsafe { tcsetattr(fd, TCSANOW, &raw_termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(RawMode { original_termios })
+    }
+
+    /// Restores the terminal attributes saved by `enable`.
+    pub fn restore(&self) -> io::Result<()> {
+        let fd = io::stdin().as_raw_fd();
+        if unsafe { tcsetattr(fd, TCSANOW, &self.original_termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Derives raw mode settings from the terminal's `original` ones.
+pub fn raw_termios(original: &Termios) -> Termios {
+    let mut raw_termios = *original;
+
+    // Disable canonical mode (ICANON), echo (ECHO),
+    // signal keys (ISIG) so Ctrl-Z and Ctrl-C reach the editor, and
+    // literal-next (IEXTEN) so Ctrl-V does too.
+    raw_termios.c_lflag &= !(ICANON | ECHO | ISIG | IEXTEN);
+    // Disable software flow control so Ctrl-S/Ctrl-Q reach the editor,
+    // and CR-to-NL translation so Enter arrives as '\r'.
+    raw_termios.c_iflag &= !(IXON | ICRNL);
+    // Let read return with no input after 100ms, so the main loop can
+    // expire status messages and handle resizes while idle.
+    raw_termios.c_cc[VMIN] = 0; // Read may return without any bytes
+    raw_termios.c_cc[VTIME] = 1; // Timeout in tenths of a second
+
+    raw_termios
+}
+
+/// Size of the terminal as `(rows, cols)` from the `TIOCGWINSZ` ioctl, or
+/// `None` if the ioctl fails or reports a zero size.
+pub fn window_size() -> Option<(u16, u16)> {
+    let mut ws: libc::winsize = unsafe { mem::zeroed() };
+
+    if unsafe { libc::ioctl(STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } != 0
+        || ws.ws_row == 0
+        || ws.ws_col == 0
+    {
+        return None;
+    }
+    Some((ws.ws_row, ws.ws_col))
+}
+
+/// Terminal input for the main loop. Raw mode already makes its reads
+/// return with no bytes after 100ms without a key.
+pub fn input_reader() -> io::Stdin {
+    io::stdin()
+}
+
+/// Writes `bytes` straight to the stdout file descriptor.
+pub fn write_stdout(bytes: &[u8]) -> io::Result<()> {
+    let written = unsafe {
+        libc::write(STDOUT_FILENO, bytes.as_ptr() as *const c_void, bytes.len())
+    };
+
+    if written != bytes.len() as isize {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Installs a SIGWINCH handler that flags terminal resizes for the main loop.
+pub fn install_resize_handler() -> io::Result<()> {
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = handle_sigwinch as *const () as libc::sighandler_t;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+
+    if unsafe { libc::sigaction(libc::SIGWINCH, &action, ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Whether a process with id `pid` is running. Signal 0 only checks that
+/// the process exists; EPERM means it does but belongs to someone else.
+pub fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// Only async-signal-safe work is allowed here, so just raise the flag.
+extern "C" fn handle_sigwinch(_signal: libc::c_int) {
+    WINDOW_RESIZED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod raw_mode_tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_termios_reads_with_timeout() {
+        let original: Termios = unsafe { mem::zeroed() };
+
+        let raw = raw_termios(&original);
+
+        assert_eq!(raw.c_cc[VMIN], 0);
+        assert_eq!(raw.c_cc[VTIME], 1);
+    }
+
+    #[test]
+    fn test_raw_termios_disables_line_editing() {
+        let mut original: Termios = unsafe { mem::zeroed() };
+        original.c_lflag = ICANON | ECHO | ISIG | IEXTEN;
+        original.c_iflag = IXON | ICRNL;
+
+        let raw = raw_termios(&original);
+
+        assert_eq!(raw.c_lflag, 0);
+        assert_eq!(raw.c_iflag, 0);
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use windows_sys::Win32::Foundation::{
+    CloseHandle, HANDLE, INVALID_HANDLE_VALUE, STILL_ACTIVE, WAIT_OBJECT_0, WAIT_TIMEOUT,
+};
+use windows_sys::Win32::System::Console::{
+    GetConsoleMode, GetConsoleScreenBufferInfo, GetStdHandle, PeekConsoleInputW, ReadConsoleInputW,
+    SetConsoleMode, CONSOLE_MODE, CONSOLE_SCREEN_BUFFER_INFO, ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT,
+    ENABLE_PROCESSED_INPUT, ENABLE_VIRTUAL_TERMINAL_INPUT, ENABLE_VIRTUAL_TERMINAL_PROCESSING,
+    INPUT_RECORD, KEY_EVENT, STD_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+};
+
+use windows_sys::Win32::System::Threading::{
+    GetExitCodeProcess, OpenProcess, WaitForSingleObject, PROCESS_QUERY_LIMITED_INFORMATION,
+};
+
+use super::WINDOW_RESIZED;
+
+/// How often the console size is polled for changes.
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a read of console input waits for a key, like `VTIME` on Unix.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Size of `TimedStdin`'s buffer: larger than std's stdin buffer, so std
+/// reads straight into it and never holds keys the wait can't see.
+const INPUT_BUF_SIZE: usize = 16 * 1024;
+
+/// The console modes in place before raw mode was enabled.
+pub struct RawMode {
+    input_mode: CONSOLE_MODE,
+    output_mode: CONSOLE_MODE,
+}
+
+impl RawMode {
+    /// Switches the console to raw input with VT sequences enabled in both
+    /// directions, returning the modes to restore later.
+    pub fn enable() -> io::Result<Self> {
+        let input = std_handle(STD_INPUT_HANDLE)?;
+        let output = std_handle(STD_OUTPUT_HANDLE)?;
+        let input_mode = console_mode(input)?;
+        let output_mode = console_mode(output)?;
+
+        // Disable line buffering, echo and Ctrl-C processing, and deliver
+        // keys as the same VT sequences a Unix terminal sends.
+        let raw_input = (input_mode & !(ENABLE_ECHO_INPUT | ENABLE_LINE_INPUT | ENABLE_PROCESSED_INPUT))
+            | ENABLE_VIRTUAL_TERMINAL_INPUT;
+        set_console_mode(input, raw_input)?;
+        set_console_mode(output, output_mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING)?;
+
+        Ok(RawMode { input_mode, output_mode })
+    }
+
+    /// Restores the console modes saved by `enable`.
+    pub fn restore(&self) -> io::Result<()> {
+        set_console_mode(std_handle(STD_INPUT_HANDLE)?, self.input_mode)?;
+        set_console_mode(std_handle(STD_OUTPUT_HANDLE)?, self.output_mode)
+    }
+}
+
+/// Console input whose reads return no bytes after `READ_TIMEOUT` without
+/// a key, as reads in raw mode do on Unix, so the main loop keeps running
+/// while idle.
+pub struct TimedStdin {
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+impl Read for TimedStdin {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.len {
+            if !wait_for_key(READ_TIMEOUT)? {
+                return Ok(0);
+            }
+            self.len = io::stdin().read(&mut self.buf)?;
+            self.pos = 0;
+        }
+        let n = out.len().min(self.len - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Console input for the main loop, with reads that time out.
+pub fn input_reader() -> TimedStdin {
+    TimedStdin { buf: vec![0; INPUT_BUF_SIZE], pos: 0, len: 0 }
+}
+
+/// Waits up to `timeout` for a key that reading stdin would return. Focus,
+/// mouse, resize and key-release events ahead of it are dropped, since a
+/// read would skip them but block until a key came.
+fn wait_for_key(timeout: Duration) -> io::Result<bool> {
+    let input = std_handle(STD_INPUT_HANDLE)?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        let mut record: INPUT_RECORD = unsafe { std::mem::zeroed() };
+        let mut count = 0;
+        if unsafe { PeekConsoleInputW(input, &mut record, 1, &mut count) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if count > 0 {
+            if is_char_key(&record) {
+                return Ok(true);
+            }
+            if unsafe { ReadConsoleInputW(input, &mut record, 1, &mut count) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            continue;
+        }
+
+        let left = deadline.saturating_duration_since(Instant::now());
+        match unsafe { WaitForSingleObject(input, left.as_millis() as u32) } {
+            WAIT_OBJECT_0 => {}
+            WAIT_TIMEOUT => return Ok(false),
+            _ => return Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+/// Whether `record` is a key press that produces input text.
+fn is_char_key(record: &INPUT_RECORD) -> bool {
+    if u32::from(record.EventType) != KEY_EVENT {
+        return false;
+    }
+    let key = unsafe { record.Event.KeyEvent };
+    key.bKeyDown != 0 && unsafe { key.uChar.UnicodeChar } != 0
+}
+
+/// Size of the visible console window as `(rows, cols)`.
+pub fn window_size() -> Option<(u16, u16)> {
+    let output = std_handle(STD_OUTPUT_HANDLE).ok()?;
+    let mut info: CONSOLE_SCREEN_BUFFER_INFO = unsafe { std::mem::zeroed() };
+    if unsafe { GetConsoleScreenBufferInfo(output, &mut info) } == 0 {
+        return None;
+    }
+
+    let window = info.srWindow;
+    let rows = u16::try_from(window.Bottom - window.Top + 1).ok()?;
+    let cols = u16::try_from(window.Right - window.Left + 1).ok()?;
+    Some((rows, cols))
+}
+
+/// Writes `bytes` to stdout and flushes them straight away.
+pub fn write_stdout(bytes: &[u8]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(bytes)?;
+    stdout.flush()
+}
+
+/// The console has no resize signal, so a background thread polls the
+/// window size and flags changes for the main loop.
+pub fn install_resize_handler() -> io::Result<()> {
+    let mut last = window_size();
+    thread::Builder::new().name("resize-watch".to_string()).spawn(move || {
+        loop {
+            thread::sleep(RESIZE_POLL_INTERVAL);
+            let size = window_size();
+            if size != last {
+                last = size;
+                WINDOW_RESIZED.store(true, Ordering::SeqCst);
+            }
+        }
+    })?;
+    Ok(())
+}
+
+/// Whether a process with id `pid` is still running.
+pub fn process_alive(pid: u32) -> bool {
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return false;
+    }
+    let mut code = 0;
+    let running = unsafe { GetExitCodeProcess(process, &mut code) } != 0 && code == STILL_ACTIVE as u32;
+    unsafe { CloseHandle(process) };
+    running
+}
+
+fn std_handle(kind: STD_HANDLE) -> io::Result<HANDLE> {
+    let handle = unsafe { GetStdHandle(kind) };
+    if handle == INVALID_HANDLE_VALUE || handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(handle)
+}
+
+fn console_mode(handle: HANDLE) -> io::Result<CONSOLE_MODE> {
+    let mut mode = 0;
+    if unsafe { GetConsoleMode(handle, &mut mode) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(mode)
+}
+
+fn set_console_mode(handle: HANDLE, mode: CONSOLE_MODE) -> io::Result<()> {
+    if unsafe { SetConsoleMode(handle, mode) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
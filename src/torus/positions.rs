@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use super::editor::EditorState;
+
+/// Last cursor position, as `(row, col)`, of each file by absolute path.
+pub type PositionMap = BTreeMap<PathBuf, (usize, usize)>;
+
+/// Parses a saved position map. Returns `None` for malformed contents.
+pub fn parse(text: &str) -> Option<PositionMap> {
+    serde_json::from_str(text).ok()
+}
+
+/// Serializes a position map for saving.
+pub fn to_json(positions: &PositionMap) -> String {
+    // Paths that aren't valid UTF-8 can't be JSON keys; skip them rather
+    // than lose every other position.
+    let valid: BTreeMap<&str, (usize, usize)> = positions
+        .iter()
+        .filter_map(|(path, &pos)| Some((path.to_str()?, pos)))
+        .collect();
+    serde_json::to_string_pretty(&valid).unwrap_or_default()
+}
+
+/// Reads the position map at `path`, treating a missing or corrupt file
+/// as empty.
+pub fn load(path: &Path) -> PositionMap {
+    fs::read_to_string(path).ok().and_then(|text| parse(&text)).unwrap_or_default()
+}
+
+/// Writes the position map to `path`, creating its directory if needed.
+pub fn store(path: &Path, positions: &PositionMap) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, to_json(positions))
+}
+
+/// Location of the position file, or `None` if `$HOME` is not set.
+pub fn positions_path() -> Option<PathBuf> {
+    let home = env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".local/share/torus/positions.json"))
+}
+
+/// The key a file is remembered under: its absolute path if it exists.
+fn key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl EditorState {
+    /// Moves the cursor to where it was when the active buffer's file was
+    /// last left, clamped to the file's current size.
+    pub fn restore_position(&mut self) {
+        let (Some(store_path), Some(filename)) = (&self.positions_path, &self.filename) else {
+            return;
+        };
+        let Some(&(y, x)) = load(store_path).get(&key(filename)) else {
+            return;
+        };
+
+        self.cursor_y = y.min(self.num_rows().saturating_sub(1));
+        self.cursor_x = x.min(self.row_len(self.cursor_y));
+        self.scroll();
+    }
+
+    /// Records the cursor position of every buffer with a file name.
+    /// Failing to write the position file is not worth interrupting for.
+    pub fn remember_positions(&self) {
+        let Some(store_path) = &self.positions_path else {
+            return;
+        };
+
+        let mut positions = load(store_path);
+        for buffer in &self.buffers {
+            if let Some(filename) = &buffer.filename {
+                positions.insert(key(filename), (buffer.cursor_y, buffer.cursor_x));
+            }
+        }
+        let _ = store(store_path, &positions);
+    }
+}
+
+#[cfg(test)]
+mod positions_tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("torus-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_position_map_round_trips_through_json() {
+        let mut positions = PositionMap::new();
+        positions.insert(PathBuf::from("/src/main.rs"), (12, 4));
+        positions.insert(PathBuf::from("/notes.txt"), (0, 0));
+
+        let json = to_json(&positions);
+
+        assert_eq!(parse(&json), Some(positions));
+    }
+
+    #[test]
+    fn test_corrupt_or_missing_positions_are_ignored() {
+        assert_eq!(parse("{not json"), None);
+        assert_eq!(parse("{\"/a\": \"line 3\"}"), None);
+        assert!(load(&temp_path("no-such-positions.json")).is_empty());
+    }
+
+    #[test]
+    fn test_restored_position_is_clamped_to_file() {
+        let file = temp_path("remembered.txt");
+        let store_path = temp_path("positions.json");
+        fs::write(&file, "one\ntwo\nthree\n").unwrap();
+
+        let mut state = EditorState::with_size(24, 80);
+        state.positions_path = Some(store_path.clone());
+        state.open_in_new_buffer(&file);
+        (state.cursor_y, state.cursor_x) = (2, 5);
+        state.remember_positions();
+
+        fs::write(&file, "one\nlonger line\n").unwrap();
+        let mut state = EditorState::with_size(24, 80);
+        state.positions_path = Some(store_path.clone());
+        state.open_in_new_buffer(&file);
+        fs::remove_file(&file).unwrap();
+        fs::remove_file(&store_path).unwrap();
+
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 5));
+    }
+}
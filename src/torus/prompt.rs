@@ -0,0 +1,126 @@
+use std::io::Read;
+
+use super::editor::EditorState;
+use super::input_handler::{ctrl_key, editor_read_key, is_insertable, Key, BACKSPACE, ENTER};
+
+/// What a prompt should do after a key has been applied to its input line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptAction {
+    Continue,
+    Submit,
+    Cancel,
+}
+
+/// Applies `key` to the text typed into a prompt so far. Enter only submits
+/// a non-empty line; Backspace on an empty line does nothing.
+pub fn edit_prompt_buffer(buf: &mut String, key: Key) -> PromptAction {
+    match key {
+        Key::Escape => PromptAction::Cancel,
+        Key::Char(ENTER) if !buf.is_empty() => PromptAction::Submit,
+        Key::Char(c) if c == BACKSPACE || c == ctrl_key(b'h') => {
+            buf.pop();
+            PromptAction::Continue
+        }
+        Key::Delete => {
+            buf.pop();
+            PromptAction::Continue
+        }
+        Key::Char(c) if c != '\t' && is_insertable(c) => {
+            buf.push(c);
+            PromptAction::Continue
+        }
+        _ => PromptAction::Continue,
+    }
+}
+
+impl EditorState {
+    /// Reads a line of input in the message bar, showing `prompt_fmt` with
+    /// `%s` replaced by the text typed so far. After every keystroke
+    /// `callback` sees the current input and the key, so callers can react
+    /// incrementally, and may set `prompt_note` to show a remark after the
+    /// input. Returns `None` if the prompt is cancelled with Escape.
+    pub fn prompt<R, F>(&mut self, input: &mut R, prompt_fmt: &str, mut callback: F) -> Option<String>
+    where
+        R: Read,
+        F: FnMut(&mut Self, &str, Key),
+    {
+        let mut buf = String::new();
+        self.prompt_note.clear();
+
+        loop {
+            let line = prompt_fmt.replace("%s", &buf);
+            self.set_status_message(if self.prompt_note.is_empty() {
+                line
+            } else {
+                format!("{} {}", line, self.prompt_note)
+            });
+            self.scroll();
+            self.refresh_screen().ok()?;
+
+            // On a timeout, loop round to redraw so resizes are picked up.
+            let Some(key) = editor_read_key(input).ok()? else {
+                continue;
+            };
+            self.record_keys([key]);
+            let action = edit_prompt_buffer(&mut buf, key);
+            callback(self, &buf, key);
+
+            match action {
+                PromptAction::Continue => {}
+                PromptAction::Submit => {
+                    self.prompt_note.clear();
+                    self.set_status_message(String::new());
+                    return Some(buf);
+                }
+                PromptAction::Cancel => {
+                    self.prompt_note.clear();
+                    self.set_status_message(String::new());
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod prompt_tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_prompt_buffer_appends_and_submits() {
+        let mut buf = String::new();
+
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::Char('h')), PromptAction::Continue);
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::Char('i')), PromptAction::Continue);
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::Char(ENTER)), PromptAction::Submit);
+        assert_eq!(buf, "hi");
+    }
+
+    #[test]
+    fn test_edit_prompt_buffer_backspace() {
+        let mut buf = "ab".to_string();
+
+        edit_prompt_buffer(&mut buf, Key::Char(BACKSPACE));
+        assert_eq!(buf, "a");
+        edit_prompt_buffer(&mut buf, Key::Char(BACKSPACE));
+        edit_prompt_buffer(&mut buf, Key::Char(BACKSPACE));
+        assert_eq!(buf, "");
+    }
+
+    #[test]
+    fn test_edit_prompt_buffer_empty_enter_and_escape() {
+        let mut buf = String::new();
+
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::Char(ENTER)), PromptAction::Continue);
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::Escape), PromptAction::Cancel);
+    }
+
+    #[test]
+    fn test_edit_prompt_buffer_ignores_control_keys() {
+        let mut buf = "x".to_string();
+
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::Char(ctrl_key(b'a'))), PromptAction::Continue);
+        assert_eq!(edit_prompt_buffer(&mut buf, Key::ArrowUp), PromptAction::Continue);
+        assert_eq!(buf, "x");
+    }
+}
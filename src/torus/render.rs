@@ -0,0 +1,380 @@
+use std::io;
+
+use unicode_width::UnicodeWidthChar;
+
+use super::error::EditorError;
+use super::highlight::Highlight;
+use super::terminal_handler::write_stdout;
+use super::theme::Palette;
+
+/// Default column interval at which hard tabs are expanded when rendering.
+pub const TAB_STOP: usize = 8;
+
+/// Accumulates a frame of output so it reaches the terminal in one write.
+pub struct AppendBuffer {
+    buf: String,
+}
+
+impl AppendBuffer {
+    pub fn new() -> Self {
+        AppendBuffer { buf: String::new() }
+    }
+
+    /// Appends a fragment to the end of the frame.
+    pub fn append(&mut self, s: &str) {
+        self.buf.push_str(s);
+    }
+
+    /// Returns the frame accumulated so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf.as_bytes()
+    }
+
+    /// Writes the whole frame to stdout in a single syscall and empties the buffer.
+    pub fn flush(&mut self) -> Result<(), EditorError> {
+        self.flush_with(write_stdout)
+    }
+
+    /// Hands the whole frame to `write` and empties the buffer. On failure
+    /// the frame is kept and the error returned.
+    pub fn flush_with<F>(&mut self, write: F) -> Result<(), EditorError>
+    where
+        F: FnOnce(&[u8]) -> io::Result<()>,
+    {
+        write(self.as_bytes())?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// The letter shown after `^` for a control character other than tab, as
+/// in `^A` for `\x01` and `^?` for DEL.
+pub fn caret_notation(c: char) -> Option<char> {
+    match c {
+        '\t' => None,
+        '\x00'..='\x1F' => Some(char::from(c as u8 + b'@')),
+        '\x7F' => Some('?'),
+        _ => None,
+    }
+}
+
+/// Number of terminal columns `c` occupies: two for wide CJK characters
+/// and for control characters drawn in caret notation, zero for combining
+/// marks.
+pub fn char_width(c: char) -> usize {
+    if caret_notation(c).is_some() {
+        return 2;
+    }
+    c.width().unwrap_or(0)
+}
+
+/// Builds the display string for a row, expanding each tab with spaces up
+/// to the next tab stop and showing control characters in caret notation.
+pub fn render_row(row: &str, tab_stop: usize) -> String {
+    render_row_with(row, tab_stop, false)
+}
+
+/// Like `render_row`, but with `show_whitespace` spaces are drawn as `·`,
+/// tabs as `→` padded with spaces and the end of the line as `¶`. Each
+/// glyph takes the cell of the blank it stands for, so columns line up
+/// with the plain rendering.
+pub fn render_row_with(row: &str, tab_stop: usize, show_whitespace: bool) -> String {
+    let mut rendered = String::with_capacity(row.len());
+    let mut col = 0;
+
+    for c in row.chars() {
+        if c == '\t' {
+            let width = tab_stop - col % tab_stop;
+            if show_whitespace {
+                rendered.push('→');
+                rendered.extend(std::iter::repeat_n(' ', width - 1));
+            } else {
+                rendered.extend(std::iter::repeat_n(' ', width));
+            }
+            col += width;
+        } else if c == ' ' && show_whitespace {
+            rendered.push('·');
+            col += 1;
+        } else if let Some(letter) = caret_notation(c) {
+            rendered.push('^');
+            rendered.push(letter);
+            col += 2;
+        } else {
+            rendered.push(c);
+            col += char_width(c);
+        }
+    }
+    if show_whitespace {
+        rendered.push('¶');
+    }
+    rendered
+}
+
+/// Maps a logical cursor column (a char index) within `row` to its
+/// rendered column.
+pub fn cx_to_rx(row: &str, cx: usize, tab_stop: usize) -> usize {
+    row.chars().take(cx).fold(0, |rx, c| {
+        if c == '\t' {
+            rx + tab_stop - rx % tab_stop
+        } else {
+            rx + char_width(c)
+        }
+    })
+}
+
+/// Inverse of `cx_to_rx`: the char index of the character covering rendered
+/// column `rx`, or the row length if `rx` lies past its end.
+pub fn rx_to_cx(row: &str, rx: usize, tab_stop: usize) -> usize {
+    let mut cur_rx = 0;
+    for (cx, c) in row.chars().enumerate() {
+        cur_rx += if c == '\t' { tab_stop - cur_rx % tab_stop } else { char_width(c) };
+        if cur_rx > rx {
+            return cx;
+        }
+    }
+    row.chars().count()
+}
+
+/// Index into the rendered row of the char at raw index `cx`; differs from
+/// `cx` once a tab has been expanded into several spaces or a control
+/// character into two.
+pub fn cx_to_render_idx(row: &str, cx: usize, tab_stop: usize) -> usize {
+    let mut rx = 0;
+    let mut idx = 0;
+    for c in row.chars().take(cx) {
+        if c == '\t' {
+            let spaces = tab_stop - rx % tab_stop;
+            rx += spaces;
+            idx += spaces;
+        } else {
+            rx += char_width(c);
+            idx += if caret_notation(c).is_some() { 2 } else { 1 };
+        }
+    }
+    idx
+}
+
+/// Returns the display width of `s` in terminal columns.
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Returns the longest prefix of `row` that fits in `width` columns.
+pub fn truncate_to_width(row: &str, width: usize) -> &str {
+    let mut col = 0;
+    for (idx, c) in row.char_indices() {
+        col += char_width(c);
+        if col > width {
+            return &row[..idx];
+        }
+    }
+    row
+}
+
+/// Returns the cells of a rendered row visible in a `width`-column window
+/// starting `offset` columns in, each paired with its highlight (missing
+/// entries in `hl` count as `Normal`). Empty if the row ends before the
+/// window starts. A wide character cut by the left edge becomes blank padding.
+pub fn visible_cells(row: &str, hl: &[Highlight], offset: usize, width: usize) -> Vec<(char, Highlight)> {
+    let mut cells = Vec::new();
+    let mut col = 0;
+
+    for (i, c) in row.chars().enumerate() {
+        let end = col + char_width(c);
+        if end > offset + width {
+            break;
+        }
+        if col >= offset {
+            cells.push((c, hl.get(i).copied().unwrap_or(Highlight::Normal)));
+        } else if end > offset {
+            cells.extend(std::iter::repeat_n((' ', Highlight::Normal), end - offset));
+        }
+        col = end;
+    }
+    cells
+}
+
+/// Draws the visible part of a rendered row in `palette`'s colours,
+/// switching them as the highlight changes and resetting the foreground
+/// colour at the end. `background` starts the row's own background, if it
+/// has one, which is set again wherever a class turns its background off
+/// and stays set at the end.
+pub fn draw_row(
+    buf: &mut AppendBuffer,
+    row: &str,
+    hl: &[Highlight],
+    offset: usize,
+    width: usize,
+    palette: &Palette,
+    background: &str,
+) {
+    let mut line = String::from(background);
+    let mut current = Highlight::Normal;
+
+    for (c, class) in visible_cells(row, hl, offset, width) {
+        if class != current {
+            if let Some(off) = current.off_code() {
+                // e.g. \x1B[27m - Reverse video off
+                line.push_str(&format!("\x1B[{}m", off));
+                line.push_str(background);
+            }
+            line.push_str(palette.sgr(class));
+            current = class;
+        }
+        line.push(c);
+    }
+    if let Some(off) = current.off_code() {
+        line.push_str(&format!("\x1B[{}m", off));
+        line.push_str(background);
+    } else if current != Highlight::Normal {
+        // \x1B[39m - Default foreground colour
+        line.push_str("\x1B[39m");
+    }
+    buf.append(&line);
+}
+
+#[cfg(test)]
+mod render_tests {
+    use super::*;
+
+    /// Text of the visible cells, ignoring their highlight.
+    fn visible_slice(row: &str, offset: usize, width: usize) -> String {
+        visible_cells(row, &[], offset, width).into_iter().map(|(c, _)| c).collect()
+    }
+
+    #[test]
+    fn test_flush_write_failure_is_an_editor_error() {
+        let mut buf = AppendBuffer::new();
+        buf.append("frame");
+
+        let result = buf.flush_with(|_| Err(io::Error::from(io::ErrorKind::BrokenPipe)));
+
+        assert!(matches!(result, Err(EditorError::Io(ref err)) if err.kind() == io::ErrorKind::BrokenPipe));
+        assert_eq!(buf.as_bytes(), b"frame");
+    }
+
+    #[test]
+    fn test_append_concatenates_fragments() {
+        let mut buf = AppendBuffer::new();
+
+        buf.append("\x1B[H");
+        buf.append("hello");
+        buf.append(", world");
+
+        assert_eq!(buf.as_bytes(), b"\x1B[Hhello, world");
+    }
+
+    #[test]
+    fn test_truncate_to_width() {
+        assert_eq!(truncate_to_width("abcdef", 3), "abc");
+        assert_eq!(truncate_to_width("ab", 3), "ab");
+        assert_eq!(truncate_to_width("abc", 0), "");
+    }
+
+    #[test]
+    fn test_visible_slice() {
+        assert_eq!(visible_slice("abcdef", 2, 3), "cde");
+        assert_eq!(visible_slice("abcdef", 4, 3), "ef");
+        assert_eq!(visible_slice("abc", 10, 3), "");
+    }
+
+    #[test]
+    fn test_render_row_expands_tabs_to_tab_stop() {
+        assert_eq!(render_row("a\tb", TAB_STOP), "a       b");
+        assert_eq!(render_row("\t", TAB_STOP), " ".repeat(TAB_STOP));
+        assert_eq!(render_row("no tabs", TAB_STOP), "no tabs");
+    }
+
+    #[test]
+    fn test_cx_to_rx() {
+        assert_eq!(cx_to_rx("a\tb", 0, TAB_STOP), 0);
+        assert_eq!(cx_to_rx("a\tb", 1, TAB_STOP), 1);
+        assert_eq!(cx_to_rx("a\tb", 2, TAB_STOP), 8);
+        assert_eq!(cx_to_rx("a\tb", 3, TAB_STOP), 9);
+        assert_eq!(cx_to_rx("\t\t", 2, TAB_STOP), 16);
+    }
+
+    #[test]
+    fn test_cx_to_render_idx_expands_tabs() {
+        assert_eq!(cx_to_render_idx("a\tb", 1, TAB_STOP), 1);
+        assert_eq!(cx_to_render_idx("a\tb", 2, TAB_STOP), 8);
+        assert_eq!(cx_to_render_idx("界\tb", 2, TAB_STOP), 7);
+    }
+
+    #[test]
+    fn test_draw_row_reverses_selection() {
+        let mut buf = AppendBuffer::new();
+        let hl = [Highlight::Normal, Highlight::Selection, Highlight::Normal];
+
+        draw_row(&mut buf, "abc", &hl, 0, 80, &Palette::default(), "");
+
+        assert_eq!(buf.as_bytes(), b"a\x1B[7mb\x1B[27m\x1B[39mc");
+    }
+
+    #[test]
+    fn test_draw_row_restores_background_after_match() {
+        let mut buf = AppendBuffer::new();
+        let hl = [Highlight::Keyword, Highlight::SearchMatch];
+
+        draw_row(&mut buf, "ab", &hl, 0, 80, &Palette::default(), "\x1B[40m");
+
+        assert_eq!(buf.as_bytes(), b"\x1B[40m\x1B[33ma\x1B[44mb\x1B[49m\x1B[40m");
+    }
+
+    #[test]
+    fn test_rx_to_cx() {
+        assert_eq!(rx_to_cx("a\tb", 0, TAB_STOP), 0);
+        assert_eq!(rx_to_cx("a\tb", 4, TAB_STOP), 1);
+        assert_eq!(rx_to_cx("a\tb", 8, TAB_STOP), 2);
+        assert_eq!(rx_to_cx("a\tb", 20, TAB_STOP), 3);
+        assert_eq!(rx_to_cx("界x", 1, TAB_STOP), 0);
+    }
+
+    #[test]
+    fn test_wide_chars_take_two_columns() {
+        assert_eq!(char_width('ü'), 1);
+        assert_eq!(char_width('中'), 2);
+        assert_eq!(cx_to_rx("中文x", 2, TAB_STOP), 4);
+        assert_eq!(str_width("a中"), 3);
+    }
+
+    #[test]
+    fn test_width_aware_slicing() {
+        assert_eq!(truncate_to_width("中文", 3), "中");
+        assert_eq!(visible_slice("中文字", 1, 4), " 文");
+        assert_eq!(visible_slice("ab中", 1, 2), "b");
+    }
+
+    #[test]
+    fn test_draw_row_emits_colour_changes() {
+        let row = "ab 12";
+        let mut hl = vec![Highlight::Normal; 3];
+        hl.extend([Highlight::Number; 2]);
+
+        let mut buf = AppendBuffer::new();
+        draw_row(&mut buf, row, &hl, 0, 80, &Palette::default(), "");
+
+        assert_eq!(buf.as_bytes(), b"ab \x1B[31m12\x1B[39m");
+    }
+
+    #[test]
+    fn test_control_chars_render_in_caret_notation() {
+        assert_eq!(render_row("a\x01b", TAB_STOP), "a^Ab");
+        assert_eq!(render_row("\x7F\x1B", TAB_STOP), "^?^[");
+        assert_eq!(char_width('\x01'), 2);
+        assert_eq!(cx_to_rx("a\x01b", 2, TAB_STOP), 3);
+        assert_eq!(cx_to_render_idx("a\x01b", 2, TAB_STOP), 3);
+        assert_eq!(rx_to_cx("a\x01b", 2, TAB_STOP), 1);
+    }
+
+    #[test]
+    fn test_render_row_with_visible_whitespace() {
+        let shown = render_row_with("a\tb ", TAB_STOP, true);
+
+        assert_eq!(shown, "a→      b·¶");
+        assert!(shown.contains('→') && shown.contains('·'));
+        // Same cells as the plain rendering, plus the end-of-line mark.
+        assert_eq!(str_width(&shown), str_width(&render_row("a\tb ", TAB_STOP)) + 1);
+        assert_eq!(render_row_with("a\tb ", TAB_STOP, false), render_row("a\tb ", TAB_STOP));
+    }
+}
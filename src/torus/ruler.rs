@@ -0,0 +1,100 @@
+use super::editor::EditorState;
+use super::highlight::Highlight;
+use super::render::{AppendBuffer, char_width};
+
+/// Screen column (0-based) of the ruler at 1-based text column `ruler`, in
+/// a pane whose text starts after a `gutter`-column gutter and shows
+/// `width` render columns from `offset`. `None` when the ruler is scrolled
+/// off either side, or is column 0.
+pub fn ruler_screen_col(ruler: usize, gutter: usize, offset: usize, width: usize) -> Option<usize> {
+    let rx = ruler.checked_sub(1)?;
+    (rx >= offset && rx < offset + width).then(|| gutter + rx - offset)
+}
+
+/// The character of `rendered` at render column `rx` with its highlight,
+/// or a blank past the end of the row. `None` inside a wide character,
+/// which the ruler would cut in half.
+pub fn cell_at(rendered: &str, hl: &[Highlight], rx: usize) -> Option<(char, Highlight)> {
+    let mut col = 0;
+    for (i, c) in rendered.chars().enumerate() {
+        if col == rx {
+            return Some((c, hl.get(i).copied().unwrap_or(Highlight::Normal)));
+        }
+        col += char_width(c);
+        if col > rx {
+            return None;
+        }
+    }
+    Some((' ', Highlight::Normal))
+}
+
+/// The cell under the ruler on one screen row: its screen column, and the
+/// character and highlight to redraw there.
+pub type RulerCell = (usize, char, Highlight);
+
+impl EditorState {
+    /// The ruler's cell on a row showing `rendered` from render column
+    /// `offset`, if the ruler is set and on screen.
+    pub fn ruler_cell(
+        &self,
+        rendered: &str,
+        hl: &[Highlight],
+        gutter: usize,
+        offset: usize,
+        width: usize,
+    ) -> Option<RulerCell> {
+        let col = ruler_screen_col(self.config.ruler_column?, gutter, offset, width)?;
+        let (c, class) = cell_at(rendered, hl, col - gutter + offset)?;
+        Some((col, c, class))
+    }
+
+    /// Redraws the ruler's cell of the current row over its background
+    /// colour, keeping the character's own colour.
+    pub fn draw_ruler(&self, buf: &mut AppendBuffer, (col, c, class): RulerCell) {
+        // \x1B[{n}G - Move to column n of the line
+        buf.append(&format!("\x1B[{}G", col + 1));
+        buf.append(self.palette.sgr(class));
+        buf.append(self.palette.sgr(Highlight::Ruler));
+        buf.append(&c.to_string());
+        // \x1B[m - Back to normal attributes
+        buf.append("\x1B[m");
+    }
+}
+
+#[cfg(test)]
+mod ruler_tests {
+    use super::*;
+
+    #[test]
+    fn test_ruler_screen_col_accounts_for_gutter_and_offset() {
+        assert_eq!(ruler_screen_col(80, 0, 0, 100), Some(79));
+        assert_eq!(ruler_screen_col(80, 4, 0, 96), Some(83));
+        assert_eq!(ruler_screen_col(80, 4, 10, 96), Some(73));
+        // Scrolled past, or beyond the right edge.
+        assert_eq!(ruler_screen_col(80, 4, 80, 96), None);
+        assert_eq!(ruler_screen_col(80, 0, 0, 79), None);
+        assert_eq!(ruler_screen_col(80, 0, 0, 80), Some(79));
+        assert_eq!(ruler_screen_col(0, 0, 0, 80), None);
+    }
+
+    #[test]
+    fn test_cell_at_column() {
+        assert_eq!(cell_at("abc", &[Highlight::Number; 3], 1), Some(('b', Highlight::Number)));
+        assert_eq!(cell_at("abc", &[], 5), Some((' ', Highlight::Normal)));
+        assert_eq!(cell_at("中x", &[], 1), None);
+        assert_eq!(cell_at("中x", &[], 2), Some(('x', Highlight::Normal)));
+    }
+
+    #[test]
+    fn test_ruler_drawn_over_row() {
+        let mut state = EditorState::with_size(2, 10);
+        state.config.ruler_column = Some(3);
+        state.rows = vec!["abcd".to_string()];
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        let out = String::from_utf8_lossy(buf.as_bytes()).into_owned();
+        assert_eq!(out, "abcd\x1B[K\x1B[3G\x1B[39m\x1B[100mc\x1B[m\r\n~\x1B[K\r\n");
+    }
+}
@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+/// Glyph of the part of the scrollbar standing for the rows on screen.
+pub const SCROLLBAR_THUMB: &str = "█";
+
+/// Glyph of the rest of the scrollbar.
+pub const SCROLLBAR_TRACK: &str = "░";
+
+/// Rows of a `height`-row scrollbar taken by the thumb, for a view
+/// starting at `row_offset` in a buffer of `num_rows` rows. The thumb's
+/// size is the share of the buffer on screen, at least one row, and it
+/// only touches the bottom once the last row is in view. `None` when the
+/// whole buffer fits, so no scrollbar is needed.
+pub fn scroll_thumb(row_offset: usize, height: usize, num_rows: usize) -> Option<Range<usize>> {
+    if num_rows <= height || height == 0 {
+        return None;
+    }
+    let len = (height * height / num_rows).clamp(1, height);
+    let start = if row_offset + height >= num_rows {
+        height - len
+    } else {
+        // Rounding down keeps a view that isn't at the end off the bottom,
+        // unless the thumb fills the whole bar.
+        (row_offset * height / num_rows).min((height - len).saturating_sub(1))
+    };
+    Some(start..start + len)
+}
+
+#[cfg(test)]
+mod scrollbar_tests {
+    use super::*;
+
+    #[test]
+    fn test_no_thumb_when_file_fits() {
+        assert_eq!(scroll_thumb(0, 20, 20), None);
+        assert_eq!(scroll_thumb(0, 20, 3), None);
+    }
+
+    #[test]
+    fn test_thumb_size_is_share_on_screen() {
+        assert_eq!(scroll_thumb(0, 20, 40), Some(0..10));
+        assert_eq!(scroll_thumb(0, 20, 100), Some(0..4));
+        assert_eq!(scroll_thumb(0, 10, 100_000), Some(0..1));
+    }
+
+    #[test]
+    fn test_thumb_position_follows_offset() {
+        assert_eq!(scroll_thumb(50, 20, 100), Some(10..14));
+        assert_eq!(scroll_thumb(80, 20, 100), Some(16..20));
+        // One row short of the end stays off the bottom row.
+        assert_eq!(scroll_thumb(79, 20, 100), Some(15..19));
+        // Past the end, as when the last row is scrolled to the top.
+        assert_eq!(scroll_thumb(99, 20, 100), Some(16..20));
+    }
+
+    #[test]
+    fn test_one_row_pane_is_all_thumb() {
+        assert_eq!(scroll_thumb(0, 1, 5), Some(0..1));
+        assert_eq!(scroll_thumb(4, 1, 5), Some(0..1));
+    }
+}
@@ -0,0 +1,444 @@
+use std::io::Read;
+
+use regex::{Regex, RegexBuilder};
+
+use super::editor::{byte_index, char_count, EditorState};
+use super::input_handler::{ctrl_key, editor_read_key, Key, ENTER};
+use super::undo::EditOp;
+
+/// A search query, compiled for matching against rows.
+#[derive(Debug, Clone)]
+pub enum SearchPattern {
+    Plain(String),
+    Regex(Regex),
+}
+
+impl SearchPattern {
+    /// Compiles `query`, as a regular expression if `regex` is set. A
+    /// case-insensitive plain query becomes an escaped regex, so both kinds
+    /// share the regex engine's Unicode case folding.
+    pub fn new(query: &str, regex: bool, ignore_case: bool) -> Result<SearchPattern, regex::Error> {
+        if !regex && !ignore_case {
+            return Ok(SearchPattern::Plain(query.to_string()));
+        }
+
+        let source = if regex { query.to_string() } else { regex::escape(query) };
+        let regex = RegexBuilder::new(&source).case_insensitive(ignore_case).build()?;
+        Ok(SearchPattern::Regex(regex))
+    }
+
+    /// True for an empty query, which matches nothing.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            SearchPattern::Plain(query) => query.is_empty(),
+            SearchPattern::Regex(regex) => regex.as_str().is_empty(),
+        }
+    }
+
+    /// Byte offset of the first match in `row` at or after byte `from`.
+    pub fn find_at(&self, row: &str, from: usize) -> Option<usize> {
+        match self {
+            SearchPattern::Plain(query) => row[from..].find(query.as_str()).map(|x| from + x),
+            SearchPattern::Regex(regex) => regex.find_at(row, from).map(|m| m.start()),
+        }
+    }
+
+    /// Byte ranges of all non-overlapping matches in `row`.
+    pub fn matches(&self, row: &str) -> Vec<(usize, usize)> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        match self {
+            SearchPattern::Plain(query) => {
+                row.match_indices(query.as_str()).map(|(x, m)| (x, x + m.len())).collect()
+            }
+            SearchPattern::Regex(regex) => regex.find_iter(row).map(|m| (m.start(), m.end())).collect(),
+        }
+    }
+
+    /// Byte offsets of all non-overlapping matches in `row`.
+    pub fn match_starts(&self, row: &str) -> Vec<usize> {
+        self.matches(row).into_iter().map(|(start, _)| start).collect()
+    }
+}
+
+/// Char column ranges `(start, end)` of the matches of `pattern` in `row`,
+/// with `end` exclusive. Empty matches are left out, having nothing to show.
+pub fn match_ranges(row: &str, pattern: &SearchPattern) -> Vec<(usize, usize)> {
+    pattern
+        .matches(row)
+        .into_iter()
+        .filter(|(start, end)| start < end)
+        .map(|(start, end)| (char_col(row, start), char_col(row, end)))
+        .collect()
+}
+
+/// Finds the first match of `pattern` at or after `start` (a `(row, col)`
+/// position), wrapping around to the top of the buffer. Returns `(row, col)`.
+/// Columns are char indices, like the cursor's.
+pub fn find_in_rows(rows: &[String], pattern: &SearchPattern, start: (usize, usize)) -> Option<(usize, usize)> {
+    if pattern.is_empty() || rows.is_empty() {
+        return None;
+    }
+
+    let start_row = start.0.min(rows.len() - 1);
+    // One extra pass revisits the start row from column 0 to catch matches
+    // that lie before `start` on the same line.
+    for i in 0..=rows.len() {
+        let y = (start_row + i) % rows.len();
+        let row = &rows[y];
+        let from = if i == 0 { byte_index(row, start.1) } else { 0 };
+
+        if let Some(x) = pattern.find_at(row, from) {
+            return Some((y, char_col(row, x)));
+        }
+    }
+    None
+}
+
+/// Finds the last match of `pattern` starting before `start`, wrapping
+/// around to the bottom of the buffer. Returns `(row, col)`.
+pub fn find_in_rows_backward(
+    rows: &[String],
+    pattern: &SearchPattern,
+    start: (usize, usize),
+) -> Option<(usize, usize)> {
+    if pattern.is_empty() || rows.is_empty() {
+        return None;
+    }
+
+    let start_row = start.0.min(rows.len() - 1);
+    for i in 0..=rows.len() {
+        let y = (start_row + rows.len() - i % rows.len()) % rows.len();
+        let row = &rows[y];
+        let before = if i == 0 { byte_index(row, start.1) } else { usize::MAX };
+
+        let found = pattern.match_starts(row).into_iter().take_while(|&x| x < before).last();
+        if let Some(x) = found {
+            return Some((y, char_col(row, x)));
+        }
+    }
+    None
+}
+
+/// Replaces every occurrence of `from` in `rows` with `to`, scanning each
+/// row left to right so occurrences don't overlap and the replacement text
+/// is never searched again. Returns the number of replacements.
+pub fn replace_all_in_rows(rows: &mut [String], from: &str, to: &str) -> usize {
+    if from.is_empty() {
+        return 0;
+    }
+
+    let mut count = 0;
+    for row in rows.iter_mut() {
+        let found = row.matches(from).count();
+        if found > 0 {
+            *row = row.replace(from, to);
+            count += found;
+        }
+    }
+    count
+}
+
+/// Converts a byte offset within `row` to a char index.
+fn char_col(row: &str, byte: usize) -> usize {
+    row[..byte].chars().count()
+}
+
+impl EditorState {
+    /// Runs an incremental search prompt. Each keystroke jumps to the next
+    /// match; the arrow keys step between matches, Ctrl-R toggles regex
+    /// mode and Alt-C toggles ignoring case. Enter keeps the cursor at the
+    /// match, Escape restores the original cursor and scroll position.
+    pub fn find<R: Read>(&mut self, input: &mut R) {
+        let saved = (self.cursor_x, self.cursor_y, self.col_offset, self.row_offset);
+        let origin = (self.cursor_y, self.cursor_x);
+        let mut last_match: Option<(usize, usize)> = None;
+
+        let query = self.prompt(input, "Search: %s (Use ESC/Arrows/Enter, Ctrl-R regex, Alt-C case)", |state, query, key| {
+            match key {
+                Key::Char(c) if c == ctrl_key(b'r') => state.search_regex = !state.search_regex,
+                Key::Alt('c') => state.search_ignore_case = !state.search_ignore_case,
+                _ => {}
+            }
+            state.prompt_note = state.search_modes();
+
+            let pattern = match SearchPattern::new(query, state.search_regex, state.search_ignore_case) {
+                Ok(pattern) => pattern,
+                Err(err) => {
+                    state.search_pattern = None;
+                    // The last line of a regex error names the problem.
+                    let reason = err.to_string().lines().last().unwrap_or_default().to_string();
+                    state.prompt_note = format!("{} {}", state.prompt_note, reason.trim());
+                    return;
+                }
+            };
+            let found = match key {
+                Key::Escape | Key::Char(ENTER) => return,
+                Key::ArrowRight | Key::ArrowDown => {
+                    let start = last_match.map_or(origin, |(y, x)| (y, x + 1));
+                    find_in_rows(&state.rows, &pattern, start)
+                }
+                Key::ArrowLeft | Key::ArrowUp => {
+                    find_in_rows_backward(&state.rows, &pattern, last_match.unwrap_or(origin))
+                }
+                // Re-search from the current match so it is kept while it still fits.
+                _ => find_in_rows(&state.rows, &pattern, last_match.unwrap_or(origin)),
+            };
+
+            state.search_pattern = Some(pattern);
+            last_match = found;
+            if let Some((y, x)) = found {
+                state.cursor_y = y;
+                state.cursor_x = x;
+            }
+        });
+
+        self.search_pattern = None;
+        if query.is_none() {
+            (self.cursor_x, self.cursor_y, self.col_offset, self.row_offset) = saved;
+        } else if (self.cursor_y, self.cursor_x) != origin {
+            self.jumps.push(origin);
+        }
+    }
+
+    /// Tags for the search modes in effect, shown after the search prompt.
+    fn search_modes(&self) -> String {
+        let mut modes = Vec::new();
+        if self.search_regex {
+            modes.push("[regex]");
+        }
+        if self.search_ignore_case {
+            modes.push("[ignore case]");
+        }
+        modes.join(" ")
+    }
+
+    /// Prompts for a search term and its replacement, then steps through the
+    /// matches from the cursor to the end of the buffer, asking whether to
+    /// replace each one, all of the rest, or none of the rest.
+    pub fn replace<R: Read>(&mut self, input: &mut R) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(from) = self.prompt(input, "Replace: %s (ESC to cancel)", |_, _, _| {}) else {
+            return;
+        };
+        let Some(to) = self.prompt(input, "Replace with: %s (ESC to cancel)", |_, _, _| {}) else {
+            return;
+        };
+
+        let pattern = SearchPattern::Plain(from.clone());
+        let mut count = 0;
+        let mut pos = (self.cursor_y, self.cursor_x);
+        // `find_in_rows` wraps around; a match before the last one means the
+        // end of the buffer has been passed.
+        while let Some(found) = find_in_rows(&self.rows, &pattern, pos).filter(|&found| found >= pos) {
+            (self.cursor_y, self.cursor_x) = found;
+            match self.ask_replace(input) {
+                Some('y') => {
+                    self.replace_rest(found, &from, &to, false);
+                    count += 1;
+                    // Skip over the replacement, whatever its length.
+                    pos = (found.0, found.1 + char_count(&to));
+                }
+                Some('n') => pos = (found.0, found.1 + 1),
+                Some('a') => {
+                    count += self.replace_rest(found, &from, &to, true);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.set_status_message(format!("Replaced {} occurrences", count));
+    }
+
+    /// Shows the replace question with the cursor on the match and waits
+    /// for an answer. Returns `None` on Escape or a read error.
+    fn ask_replace<R: Read>(&mut self, input: &mut R) -> Option<char> {
+        self.set_status_message("Replace? (y)es (n)o (a)ll (q)uit".to_string());
+        loop {
+            self.scroll();
+            self.refresh_screen().ok()?;
+            match editor_read_key(input).ok()? {
+                Some(Key::Char(c)) if "ynaq".contains(c) => return Some(c),
+                Some(Key::Escape) => return None,
+                _ => {}
+            }
+        }
+    }
+
+    /// Replaces the occurrence of `from` at `at`, or with `all` every
+    /// occurrence from there to the end of the buffer, as one undo unit.
+    /// Returns the number of replacements.
+    fn replace_rest(&mut self, at: (usize, usize), from: &str, to: &str, all: bool) -> usize {
+        let (y, x) = at;
+        let row = &self.rows[y];
+        let (head, tail) = row.split_at(byte_index(row, x));
+        let end = if all { self.num_rows() } else { y + 1 };
+
+        let mut rest: Vec<String> = self.rows[y..end].to_vec();
+        rest[0] = tail.to_string();
+        let count = if all {
+            replace_all_in_rows(&mut rest, from, to)
+        } else {
+            rest[0] = rest[0].replacen(from, to, 1);
+            1
+        };
+        rest[0].insert_str(0, head);
+
+        let mut ops = Vec::new();
+        for (i, new) in rest.into_iter().enumerate() {
+            let old = &self.rows[y + i];
+            if *old != new {
+                ops.push(EditOp::RemoveRow { row: y + i, text: old.clone() });
+                ops.push(EditOp::InsertRow { row: y + i, text: new });
+            }
+        }
+        self.edit_all(ops, (y, x + char_count(to)));
+        count
+    }
+}
+
+#[cfg(test)]
+mod search_tests {
+    use super::*;
+
+    fn rows(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    fn plain(query: &str) -> SearchPattern {
+        SearchPattern::Plain(query.to_string())
+    }
+
+    #[test]
+    fn test_find_in_rows_from_start() {
+        let rows = rows(&["alpha", "beta", "gamma beta"]);
+
+        assert_eq!(find_in_rows(&rows, &plain("beta"), (0, 0)), Some((1, 0)));
+        assert_eq!(find_in_rows(&rows, &plain("beta"), (1, 1)), Some((2, 6)));
+    }
+
+    #[test]
+    fn test_find_in_rows_wraps_around() {
+        let rows = rows(&["needle here", "nothing", "still nothing"]);
+
+        assert_eq!(find_in_rows(&rows, &plain("needle"), (1, 3)), Some((0, 0)));
+        // Wrapping all the way back to the start row finds earlier columns.
+        assert_eq!(find_in_rows(&rows, &plain("needle"), (0, 4)), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_find_in_rows_no_match() {
+        let rows = rows(&["alpha", "beta"]);
+
+        assert_eq!(find_in_rows(&rows, &plain("delta"), (0, 0)), None);
+        assert_eq!(find_in_rows(&rows, &plain(""), (0, 0)), None);
+        assert_eq!(find_in_rows(&[], &plain("alpha"), (0, 0)), None);
+    }
+
+    #[test]
+    fn test_find_in_rows_backward_wraps_around() {
+        let rows = rows(&["ab ab", "cd", "ab"]);
+
+        assert_eq!(find_in_rows_backward(&rows, &plain("ab"), (0, 3)), Some((0, 0)));
+        assert_eq!(find_in_rows_backward(&rows, &plain("ab"), (0, 0)), Some((2, 0)));
+        assert_eq!(find_in_rows_backward(&rows, &plain("ab"), (2, 0)), Some((0, 3)));
+        assert_eq!(find_in_rows_backward(&rows, &plain("zz"), (1, 0)), None);
+    }
+
+    #[test]
+    fn test_find_in_rows_uses_char_columns() {
+        let rows = rows(&["héllo wörld"]);
+
+        assert_eq!(find_in_rows(&rows, &plain("wörld"), (0, 0)), Some((0, 6)));
+        assert_eq!(find_in_rows(&rows, &plain("l"), (0, 4)), Some((0, 9)));
+        assert_eq!(find_in_rows_backward(&rows, &plain("l"), (0, 9)), Some((0, 3)));
+    }
+
+    #[test]
+    fn test_replace_all_in_rows_with_different_lengths() {
+        let mut rows = rows(&["a.b.c", "none", "..."]);
+
+        assert_eq!(replace_all_in_rows(&mut rows, ".", "::"), 5);
+        assert_eq!(rows, vec!["a::b::c", "none", "::::::"]);
+
+        assert_eq!(replace_all_in_rows(&mut rows, "::", ""), 5);
+        assert_eq!(rows, vec!["abc", "none", ""]);
+    }
+
+    #[test]
+    fn test_replace_all_in_rows_overlapping_matches() {
+        let mut rows = rows(&["aaa", "aaaa"]);
+
+        // Matches don't overlap: "aaa" holds one "aa", not two.
+        assert_eq!(replace_all_in_rows(&mut rows, "aa", "b"), 3);
+        assert_eq!(rows, vec!["ba", "bb"]);
+
+        // A replacement containing the term is not replaced again.
+        assert_eq!(replace_all_in_rows(&mut rows, "b", "bb"), 3);
+        assert_eq!(rows, vec!["bba", "bbbb"]);
+        assert_eq!(replace_all_in_rows(&mut rows, "", "x"), 0);
+    }
+
+    #[test]
+    fn test_replace_rest_from_cursor_is_one_undo_unit() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = rows(&["x x", "x", "yx"]);
+
+        assert_eq!(state.replace_rest((0, 2), "x", "long", true), 3);
+        assert_eq!(state.rows, vec!["x long", "long", "ylong"]);
+        assert_eq!((state.cursor_y, state.cursor_x), (0, 6));
+
+        state.undo();
+        assert_eq!(state.rows, vec!["x x", "x", "yx"]);
+    }
+
+    #[test]
+    fn test_regex_pattern_finds_function_names() {
+        let pattern = SearchPattern::new(r"\bfn\s+\w+", true, false).unwrap();
+        let rows = rows(&["let f = 1;", "pub fn  main() {}", "fn_ptr(); fn helper"]);
+
+        assert_eq!(pattern.find_at(&rows[1], 0), Some(4));
+        assert_eq!(pattern.match_starts(&rows[2]), vec![10]);
+        assert_eq!(find_in_rows(&rows, &pattern, (0, 0)), Some((1, 4)));
+        assert_eq!(find_in_rows(&rows, &pattern, (1, 5)), Some((2, 10)));
+        assert_eq!(find_in_rows_backward(&rows, &pattern, (2, 10)), Some((1, 4)));
+    }
+
+    #[test]
+    fn test_invalid_regex_is_an_error() {
+        assert!(SearchPattern::new("fn (", true, false).is_err());
+        assert!(SearchPattern::new("fn (", false, false).is_ok());
+        assert!(SearchPattern::new("fn (", false, true).is_ok());
+    }
+
+    #[test]
+    fn test_ignore_case_flag() {
+        let rows = rows(&["FOO", "Foo", "foo"]);
+
+        let pattern = SearchPattern::new("foo", false, true).unwrap();
+        assert_eq!(find_in_rows(&rows, &pattern, (0, 0)), Some((0, 0)));
+        assert_eq!(find_in_rows(&rows, &pattern, (1, 0)), Some((1, 0)));
+        assert_eq!(find_in_rows_backward(&rows, &pattern, (0, 0)), Some((2, 0)));
+
+        let pattern = SearchPattern::new("foo", false, false).unwrap();
+        assert_eq!(find_in_rows(&rows, &pattern, (0, 0)), Some((2, 0)));
+
+        // Regex metacharacters in a plain query stay literal.
+        let pattern = SearchPattern::new("F.O", false, true).unwrap();
+        assert_eq!(find_in_rows(&rows, &pattern, (0, 0)), None);
+    }
+
+    #[test]
+    fn test_match_ranges_in_row() {
+        assert_eq!(match_ranges("ab cab ab", &plain("ab")), vec![(0, 2), (4, 6), (7, 9)]);
+        assert_eq!(match_ranges("héllo héllo", &plain("llo")), vec![(2, 5), (8, 11)]);
+        assert_eq!(match_ranges("abc", &plain("x")), vec![]);
+        assert_eq!(match_ranges("abc", &plain("")), vec![]);
+
+        let pattern = SearchPattern::new(r"\d+|x*", true, false).unwrap();
+        assert_eq!(match_ranges("a12b3", &pattern), vec![(1, 3), (4, 5)]);
+    }
+}
@@ -0,0 +1,103 @@
+use super::editor::{byte_index, EditorState};
+
+impl EditorState {
+    /// Starts a selection anchored at the cursor, or drops the current one.
+    pub fn toggle_selection(&mut self) {
+        self.selection_anchor = match self.selection_anchor {
+            Some(_) => None,
+            None => Some((self.cursor_y, self.cursor_x)),
+        };
+    }
+
+    /// Ordered `(start, end)` positions of the selection, as `(row, col)`
+    /// pairs with `end` exclusive.
+    pub fn selection_range(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.selection_anchor?;
+        let cursor = (self.cursor_y, self.cursor_x);
+        Some((anchor.min(cursor), anchor.max(cursor)))
+    }
+
+    /// Char columns of row `y` covered by the selection. Lines wholly inside
+    /// a multi-line selection run to their end.
+    pub fn selected_cols(&self, y: usize) -> Option<(usize, usize)> {
+        let ((start_y, start_x), (end_y, end_x)) = self.selection_range()?;
+        if y < start_y || y > end_y {
+            return None;
+        }
+
+        let from = if y == start_y { start_x } else { 0 };
+        let to = if y == end_y { end_x } else { self.row_len(y) };
+        Some((from, to))
+    }
+
+    /// First and last rows the selection touches, leaving out a last row
+    /// that it only reaches the start of. `None` without a selection.
+    pub fn selected_rows(&self) -> Option<(usize, usize)> {
+        let ((start_y, _), (end_y, end_x)) = self.selection_range()?;
+        let last = if end_y > start_y && end_x == 0 { end_y - 1 } else { end_y };
+        Some((start_y, last))
+    }
+
+    /// Text from the selection anchor to the cursor, with rows joined by
+    /// newlines. Empty if there is no selection.
+    pub fn selected_text(&self) -> String {
+        let Some(((start_y, _), (end_y, _))) = self.selection_range() else {
+            return String::new();
+        };
+
+        let mut lines = Vec::new();
+        for y in start_y..=end_y.min(self.num_rows().saturating_sub(1)) {
+            let row = &self.rows[y];
+            let (from, to) = self.selected_cols(y).unwrap_or_default();
+            lines.push(&row[byte_index(row, from)..byte_index(row, to)]);
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod selection_tests {
+    use super::*;
+
+    fn state_with(lines: &[&str]) -> EditorState {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = lines.iter().map(|line| line.to_string()).collect();
+        state
+    }
+
+    #[test]
+    fn test_selected_text_across_two_lines() {
+        let mut state = state_with(&["hello world", "second line"]);
+        state.cursor_x = 6;
+        state.toggle_selection();
+        state.cursor_y = 1;
+        state.cursor_x = 6;
+
+        assert_eq!(state.selected_text(), "world\nsecond");
+    }
+
+    #[test]
+    fn test_selected_text_with_cursor_before_anchor() {
+        let mut state = state_with(&["héllo", "wörld"]);
+        state.cursor_y = 1;
+        state.cursor_x = 2;
+        state.toggle_selection();
+        state.cursor_y = 0;
+        state.cursor_x = 1;
+
+        assert_eq!(state.selected_text(), "éllo\nwö");
+    }
+
+    #[test]
+    fn test_toggle_selection_clears_it() {
+        let mut state = state_with(&["abc"]);
+
+        state.toggle_selection();
+        state.cursor_x = 2;
+        assert_eq!(state.selected_text(), "ab");
+
+        state.toggle_selection();
+        assert_eq!(state.selection_range(), None);
+        assert_eq!(state.selected_text(), "");
+    }
+}
@@ -0,0 +1,81 @@
+use super::editor::{char_count, EditorState};
+
+/// Counts for the whole buffer or the selection, as shown by `:wc`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub lines: usize,
+    /// Runs of non-whitespace characters.
+    pub words: usize,
+    pub chars: usize,
+    /// Size in UTF-8.
+    pub bytes: usize,
+}
+
+/// Counts `rows` as text with a newline between each pair of them, which
+/// the character and byte counts include.
+pub fn buffer_stats(rows: &[String]) -> Stats {
+    let newlines = rows.len().saturating_sub(1);
+    let mut stats = Stats { lines: rows.len(), chars: newlines, bytes: newlines, ..Stats::default() };
+    for row in rows {
+        stats.words += row.split_whitespace().count();
+        stats.chars += char_count(row);
+        stats.bytes += row.len();
+    }
+    stats
+}
+
+impl EditorState {
+    /// Reports the counts of the selection, or of the whole buffer without
+    /// one, in the message bar.
+    pub fn show_stats(&mut self) {
+        let (scope, stats) = if self.selection_anchor.is_some() {
+            let rows: Vec<String> = self.selected_text().split('\n').map(str::to_string).collect();
+            ("Selection", buffer_stats(&rows))
+        } else {
+            ("Buffer", buffer_stats(&self.rows))
+        };
+        self.set_status_message(format!(
+            "{}: {} lines, {} words, {} chars, {} bytes",
+            scope, stats.lines, stats.words, stats.chars, stats.bytes
+        ));
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    fn rows(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_counts_words_chars_and_bytes() {
+        let stats = buffer_stats(&rows(&["one two", "  three\tfour ", ""]));
+
+        assert_eq!(stats, Stats { lines: 3, words: 4, chars: 22, bytes: 22 });
+    }
+
+    #[test]
+    fn test_multibyte_chars_count_once() {
+        let stats = buffer_stats(&rows(&["naïve café", "中文"]));
+
+        assert_eq!(stats, Stats { lines: 2, words: 3, chars: 13, bytes: 19 });
+        assert_eq!(buffer_stats(&[]), Stats::default());
+    }
+
+    #[test]
+    fn test_stats_of_selection() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = rows(&["alpha beta", "gamma delta"]);
+        state.selection_anchor = Some((0, 6));
+        (state.cursor_y, state.cursor_x) = (1, 5);
+
+        state.show_stats();
+        assert_eq!(state.status_msg, "Selection: 2 lines, 2 words, 10 chars, 10 bytes");
+
+        state.selection_anchor = None;
+        state.show_stats();
+        assert_eq!(state.status_msg, "Buffer: 2 lines, 4 words, 22 chars, 22 bytes");
+    }
+}
@@ -0,0 +1,102 @@
+use super::buffer::LineEnding;
+use super::editor::EditorState;
+
+impl EditorState {
+    /// Expands the tokens of a status bar `format` for buffer `index`:
+    ///
+    /// - `{filename}`, `{modified}` (`[+]` when there are unsaved changes),
+    ///   `{readonly}` (`[RO]`) and `{eol}` (`LF` or `CRLF`)
+    /// - `{filetype}`, `{mode}` and `{lines}`
+    /// - `{line}` and `{col}`, 1-based, and `{percent}` of the way down
+    ///
+    /// Anything else, including unknown tokens, is copied as it is.
+    pub fn expand_status_format(&self, format: &str, index: usize) -> String {
+        let buffer = &self.buffers[index];
+        let mut out = String::new();
+        let mut rest = format;
+
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let token = &rest[..=end];
+            let value = match &token[1..token.len() - 1] {
+                "filename" => buffer.display_name(),
+                "modified" => if buffer.dirty { "[+]" } else { "" }.to_string(),
+                "readonly" => if buffer.read_only { "[RO]" } else { "" }.to_string(),
+                "eol" => match buffer.line_ending {
+                    LineEnding::Lf => "LF".to_string(),
+                    LineEnding::Crlf => "CRLF".to_string(),
+                },
+                "filetype" => buffer.syntax.map_or("no ft", |syntax| syntax.name).to_string(),
+                "mode" => self.mode_label(),
+                "lines" => buffer.rows.len().to_string(),
+                "line" => (buffer.cursor_y + 1).to_string(),
+                "col" => (buffer.cursor_x + 1).to_string(),
+                "percent" => match buffer.rows.len() {
+                    0 => "0%".to_string(),
+                    rows => format!("{}%", (buffer.cursor_y + 1).min(rows) * 100 / rows),
+                },
+                _ => token.to_string(),
+            };
+            out.push_str(&value);
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+#[cfg(test)]
+mod statusline_tests {
+    use super::*;
+    use crate::torus::highlight::select_syntax;
+    use crate::torus::render::AppendBuffer;
+    use std::path::PathBuf;
+
+    fn state_at_line_3_of_4() -> EditorState {
+        let mut state = EditorState::with_size(24, 80);
+        state.filename = Some(PathBuf::from("src/main.rs"));
+        state.syntax = select_syntax("main.rs");
+        state.rows = vec!["fn main() {".to_string(), String::new(), "    run();".to_string(), "}".to_string()];
+        (state.cursor_y, state.cursor_x) = (2, 4);
+        state.dirty = true;
+        state
+    }
+
+    #[test]
+    fn test_expand_known_tokens() {
+        let state = state_at_line_3_of_4();
+
+        let status = state.expand_status_format("{filename} {modified} | {filetype} | {line}:{col} {percent}", 0);
+
+        assert_eq!(status, "src/main.rs [+] | rust | 3:5 75%");
+    }
+
+    #[test]
+    fn test_unknown_tokens_render_literally() {
+        let state = state_at_line_3_of_4();
+
+        assert_eq!(state.expand_status_format("{branch} {lines} lines {", 0), "{branch} 4 lines {");
+        assert_eq!(state.expand_status_format("{mode}{readonly} {eol}", 0), "NORMAL LF");
+    }
+
+    #[test]
+    fn test_formatted_status_is_padded_and_truncated() {
+        let mut state = state_at_line_3_of_4();
+        state.screen_cols = 12;
+        state.config.status_format = Some("{filename} {line}/{lines}".to_string());
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_status(&mut buf, state.active);
+
+        assert_eq!(buf.as_bytes(), b"\x1B[7msrc/main.rs \x1B[m\r\n");
+
+        state.config.status_format = Some("{line}".to_string());
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_status(&mut buf, state.active);
+        assert_eq!(buf.as_bytes(), b"\x1B[7m3           \x1B[m\r\n");
+    }
+}
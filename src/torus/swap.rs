@@ -0,0 +1,269 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::editor::EditorState;
+use super::platform;
+
+/// Pause after the last key press before swap files are written.
+const SWAP_DELAY: Duration = Duration::from_secs(1);
+
+/// Start of a swap file's first line, which goes on to name the process
+/// that wrote it.
+const SWAP_HEADER: &str = "torus swap ";
+
+/// Whether a buffer may write its file's swap file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SwapStatus {
+    /// No one else's swap file was found; this buffer writes and removes it.
+    #[default]
+    Owned,
+    /// A swap file left by an editor that has exited, waiting for
+    /// `:recover` or `:discard`. It is left alone until then.
+    Recoverable,
+    /// A swap file of a running editor with this process id, which already
+    /// has the file open. It is never touched.
+    InUse(u32),
+}
+
+/// Snapshot of a buffer read back from its swap file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Swap {
+    /// Process id of the editor that wrote it.
+    pub pid: u32,
+    pub rows: Vec<String>,
+}
+
+/// Where the swap file of `path` goes: a hidden sibling, so `dir/notes.txt`
+/// swaps to `dir/.notes.txt.swp`.
+pub fn swap_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.swp", name))
+}
+
+/// Writes `rows` as the snapshot of process `pid` to the swap file at `path`.
+pub fn write_swap(path: &Path, pid: u32, rows: &[String]) -> io::Result<()> {
+    let mut contents = format!("{}{}\n", SWAP_HEADER, pid);
+    for row in rows {
+        contents.push_str(row);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+/// Reads back a swap file written by `write_swap`.
+pub fn read_swap(path: &Path) -> io::Result<Swap> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    let pid = lines
+        .next()
+        .and_then(|header| header.strip_prefix(SWAP_HEADER))
+        .and_then(|pid| pid.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a swap file"))?;
+    Ok(Swap { pid, rows: lines.map(str::to_string).collect() })
+}
+
+impl EditorState {
+    /// Looks for a swap file left for the active buffer's file: a running
+    /// editor's means the file is open twice, anything else a crash whose
+    /// work can be recovered. An unreadable swap file is overwritten.
+    pub fn check_swap(&mut self) {
+        let Some(path) = self.filename.clone() else {
+            return;
+        };
+        let Ok(swap) = read_swap(&swap_path(&path)) else {
+            self.swap = SwapStatus::Owned;
+            return;
+        };
+
+        if platform::process_alive(swap.pid) {
+            self.swap = SwapStatus::InUse(swap.pid);
+            self.set_status_message(format!(
+                "WARNING: {} is already open in another editor (pid {})",
+                path.display(),
+                swap.pid
+            ));
+        } else {
+            self.swap = SwapStatus::Recoverable;
+            self.set_status_message(format!(
+                "Found a swap file for {}: :recover to restore it, :discard to delete it",
+                path.display()
+            ));
+        }
+    }
+
+    /// Replaces the active buffer's text with its recoverable swap file's,
+    /// as one undo unit. The swap file stays until the buffer is saved.
+    pub fn recover_swap(&mut self) -> Result<(), String> {
+        let path = self.recoverable_swap()?;
+        let swap = read_swap(&path).map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+        if !self.check_writable() {
+            return Err("File is read-only".to_string());
+        }
+
+        let len = self.num_rows();
+        self.replace_rows(0..len, swap.rows);
+        self.swap = SwapStatus::Owned;
+        self.set_status_message("Recovered from swap file; save to keep it".to_string());
+        Ok(())
+    }
+
+    /// Deletes the active buffer's recoverable swap file unread.
+    pub fn discard_swap(&mut self) -> Result<(), String> {
+        let path = self.recoverable_swap()?;
+        fs::remove_file(&path).map_err(|err| format!("Can't delete {}: {}", path.display(), err))?;
+        self.swap = SwapStatus::Owned;
+        self.set_status_message("Swap file deleted".to_string());
+        Ok(())
+    }
+
+    fn recoverable_swap(&self) -> Result<PathBuf, String> {
+        match (&self.filename, self.swap) {
+            (Some(path), SwapStatus::Recoverable) => Ok(swap_path(path)),
+            _ => Err("No swap file to recover".to_string()),
+        }
+    }
+
+    /// Called once per pass of the main loop with whether any keys were
+    /// handled in it. Once the keys pause, snapshots every modified buffer
+    /// that owns its swap file.
+    pub fn swap_tick(&mut self, active: bool, now: Instant) {
+        if active {
+            self.swap_pending_since = Some(now);
+        } else if let Some(since) = self.swap_pending_since
+            && now.saturating_duration_since(since) >= SWAP_DELAY
+        {
+            self.swap_pending_since = None;
+            self.write_swap_files();
+        }
+    }
+
+    /// Writes a swap file for every modified, named buffer that owns one.
+    pub fn write_swap_files(&mut self) {
+        let pid = std::process::id();
+        let mut failed = None;
+        for buffer in &self.buffers {
+            if let Some(path) = &buffer.filename
+                && buffer.dirty
+                && buffer.swap == SwapStatus::Owned
+                && let Err(err) = write_swap(&swap_path(path), pid, &buffer.rows)
+            {
+                failed = Some(format!("Can't write swap file for {}: {}", path.display(), err));
+            }
+        }
+        if let Some(msg) = failed {
+            self.set_status_message(msg);
+        }
+    }
+
+    /// Deletes the active buffer's swap file, if it owns one, once its text
+    /// is safely on disk.
+    pub fn remove_swap(&mut self) {
+        if let Some(path) = &self.filename
+            && self.swap == SwapStatus::Owned
+        {
+            // Usually there is none, since the buffer wasn't idle long enough.
+            let _ = fs::remove_file(swap_path(path));
+        }
+    }
+
+    /// Deletes every swap file the open buffers own, on a clean exit.
+    pub fn remove_swap_files(&mut self) {
+        let active = self.active;
+        for index in 0..self.buffers.len() {
+            self.active = index;
+            self.remove_swap();
+        }
+        self.active = active;
+    }
+}
+
+#[cfg(test)]
+mod swap_tests {
+    use super::*;
+
+    /// Above any real process id, so never running.
+    const DEAD_PID: u32 = i32::MAX as u32;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("torus-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_swap_path_is_hidden_sibling() {
+        assert_eq!(swap_path(Path::new("dir/notes.txt")), PathBuf::from("dir/.notes.txt.swp"));
+        assert_eq!(swap_path(Path::new("main.rs")), PathBuf::from(".main.rs.swp"));
+    }
+
+    #[test]
+    fn test_swap_file_round_trips() {
+        let path = temp_path("roundtrip.swp");
+        let rows = vec!["fn main() {".to_string(), String::new(), "}".to_string()];
+
+        write_swap(&path, 42, &rows).unwrap();
+        let swap = read_swap(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(swap, Swap { pid: 42, rows });
+    }
+
+    #[test]
+    fn test_open_with_stale_swap_offers_recovery() {
+        let path = temp_path("stale.txt");
+        fs::write(&path, "saved\n").unwrap();
+        write_swap(&swap_path(&path), DEAD_PID, &["unsaved".to_string()]).unwrap();
+        let mut state = EditorState::with_size(24, 80);
+
+        state.open_in_new_buffer(&path);
+        assert_eq!(state.swap, SwapStatus::Recoverable);
+        assert!(state.status_msg.contains(":recover"));
+
+        state.recover_swap().unwrap();
+        assert_eq!(state.rows, vec!["unsaved"]);
+        assert!(state.dirty);
+
+        state.save().unwrap();
+        assert!(!swap_path(&path).exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_live_swap_warns_and_leaves_it_alone() {
+        let path = temp_path("in-use.txt");
+        fs::write(&path, "text\n").unwrap();
+        let swap = swap_path(&path);
+        write_swap(&swap, std::process::id(), &["theirs".to_string()]).unwrap();
+        let mut state = EditorState::with_size(24, 80);
+
+        state.open_in_new_buffer(&path);
+        state.insert_char('x');
+        state.write_swap_files();
+        state.remove_swap_files();
+
+        assert_eq!(state.swap, SwapStatus::InUse(std::process::id()));
+        assert!(state.status_msg.contains("already open"));
+        assert_eq!(read_swap(&swap).unwrap().rows, vec!["theirs"]);
+        fs::remove_file(&swap).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_swap_written_after_pause_in_typing() {
+        let path = temp_path("pause.txt");
+        let mut state = EditorState::with_size(24, 80);
+        state.set_filename(path.clone());
+        state.insert_char('a');
+        let start = Instant::now();
+
+        state.swap_tick(true, start);
+        state.swap_tick(false, start + SWAP_DELAY / 2);
+        assert!(!swap_path(&path).exists());
+
+        state.swap_tick(false, start + SWAP_DELAY);
+        assert_eq!(read_swap(&swap_path(&path)).unwrap().rows, vec!["a"]);
+
+        state.remove_swap_files();
+        assert!(!swap_path(&path).exists());
+    }
+}
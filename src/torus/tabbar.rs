@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+use super::buffer::Buffer;
+use super::editor::EditorState;
+use super::render::{AppendBuffer, str_width};
+
+/// Label of `buffer`'s tab: its file name without the directories, with a
+/// `*` if it has unsaved changes.
+pub fn tab_label(buffer: &Buffer) -> String {
+    let name = match &buffer.filename {
+        Some(path) => path
+            .file_name()
+            .map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned()),
+        None => buffer.display_name(),
+    };
+    if buffer.dirty { format!("{}*", name) } else { name }
+}
+
+/// Columns taken by each tab of `labels`, which are drawn side by side
+/// with a space either side. Tabs that don't fit in `width` columns whole
+/// are left out.
+pub fn tab_spans(labels: &[String], width: usize) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    for label in labels {
+        let end = start + str_width(label) + 2;
+        if end > width {
+            break;
+        }
+        spans.push(start..end);
+        start = end;
+    }
+    spans
+}
+
+impl EditorState {
+    /// Rows taken by the tab bar, which is only shown while more than one
+    /// buffer is open.
+    pub fn tab_bar_rows(&self) -> usize {
+        usize::from(self.buffers.len() > 1)
+    }
+
+    fn tab_labels(&self) -> Vec<String> {
+        self.buffers.iter().map(tab_label).collect()
+    }
+
+    /// The tab bar's text, with the active buffer's tab in reverse video.
+    pub fn tab_bar(&self) -> String {
+        let labels = self.tab_labels();
+        let spans = tab_spans(&labels, self.screen_cols);
+        let mut bar = String::new();
+        for (index, label) in labels.iter().take(spans.len()).enumerate() {
+            if index == self.active {
+                // \x1B[7m - Reverse video, \x1B[m - back to normal
+                bar.push_str(&format!("\x1B[7m {} \x1B[m", label));
+            } else {
+                bar.push_str(&format!(" {} ", label));
+            }
+        }
+        bar
+    }
+
+    /// Index of the buffer whose tab covers screen column `col`.
+    pub fn tab_at(&self, col: usize) -> Option<usize> {
+        tab_spans(&self.tab_labels(), self.screen_cols).iter().position(|span| span.contains(&col))
+    }
+
+    /// Draws the tab bar across the top row, if it is shown.
+    pub fn draw_tab_bar(&self, buf: &mut AppendBuffer) {
+        if self.tab_bar_rows() == 0 {
+            return;
+        }
+        buf.append(&self.tab_bar());
+        buf.append("\x1B[K");
+        buf.append("\r\n");
+    }
+}
+
+#[cfg(test)]
+mod tabbar_tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn three_buffers() -> EditorState {
+        let mut state = EditorState::with_size(10, 40);
+        state.filename = Some(PathBuf::from("src/main.rs"));
+        state.add_buffer();
+        state.filename = Some(PathBuf::from("notes.txt"));
+        state.dirty = true;
+        state.add_buffer();
+        state
+    }
+
+    #[test]
+    fn test_tab_bar_marks_active_and_dirty_buffers() {
+        let mut state = three_buffers();
+        state.active = 1;
+
+        assert_eq!(state.tab_bar(), " main.rs \x1B[7m notes.txt* \x1B[m [No Name] ");
+        assert_eq!(state.tab_bar_rows(), 1);
+    }
+
+    #[test]
+    fn test_tabs_that_do_not_fit_are_left_out() {
+        let mut state = three_buffers();
+        state.screen_cols = 21;
+
+        assert_eq!(state.tab_bar(), " main.rs  notes.txt* ");
+        assert_eq!(state.tab_at(0), Some(0));
+        assert_eq!(state.tab_at(9), Some(1));
+        assert_eq!(state.tab_at(21), None);
+    }
+
+    #[test]
+    fn test_click_on_tab_switches_buffer() {
+        let mut state = three_buffers();
+        state.active = 0;
+
+        state.click(12, 1);
+
+        assert_eq!(state.active, 1);
+    }
+}
@@ -1,44 +1,41 @@
-use libc::{
-    tcgetattr, tcsetattr, termios as Termios, ECHO, ICANON, TCSANOW, VMIN, VTIME, STDOUT_FILENO, c_void
-};
-use std::io::{self, Read, Write};
-use std::os::fd::AsRawFd;
-use std::{mem};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use super::editor::EditorState;
+use super::error::EditorError;
+use super::input_handler::process_available_keys;
+use super::platform::{self, RawMode, WINDOW_RESIZED};
+
+pub use super::platform::{install_resize_handler, write_stdout};
 
 /// A guard that restores the terminal settings when dropped.
 struct RawModeGuard {
-    original_termios: Termios,
+    raw_mode: RawMode,
 }
 
 impl RawModeGuard {
     /// Enables raw mode for the terminal.
     fn enable_raw_mode() -> io::Result<Self> {
-        let stdin = io::stdin();
-        let fd = stdin.as_raw_fd();
+        let raw_mode = RawMode::enable()?;
 
-        let mut original_termios: Termios = unsafe { mem::zeroed() };
+        println!("Raw mode enabled.");
 
-        // Get the current terminal attributes
-        if unsafe { tcgetattr(fd, &mut original_termios) } != 0 {
-            return Err(io::Error::last_os_error());
+        // \x1B[?1000h - Report mouse clicks, \x1B[?1006h - in SGR encoding
+        if let Err(err) = write_stdout(b"\x1B[?1000h\x1B[?1006h") {
+            eprintln!("Failed to enable mouse reporting: {}", err);
         }
-
-        let mut raw_termios = original_termios.clone();
-
-        // Disable canonical mode (ICANON), echo (ECHO),
-        // and various signal processing flags.
-        raw_termios.c_lflag &= !(ICANON | ECHO);
-        raw_termios.c_cc[VMIN] = 1; // Read returns after 1 byte
-        raw_termios.c_cc[VTIME] = 0; // No timeout
-
-        // Set the new terminal attributes immediately
-        if unsafe { tcsetattr(fd, TCSANOW, &raw_termios) } != 0 {
-            return Err(io::Error::last_os_error());
+        // \x1B[?2004h - Bracket pasted text so it isn't read as keystrokes
+        if let Err(err) = write_stdout(b"\x1B[?2004h") {
+            eprintln!("Failed to enable bracketed paste: {}", err);
+        }
+        // \x1B[?1004h - Report the window gaining and losing focus
+        if let Err(err) = write_stdout(b"\x1B[?1004h") {
+            eprintln!("Failed to enable focus reporting: {}", err);
         }
 
-        println!("Raw mode enabled.");
-
-        Ok(RawModeGuard { original_termios })
+        Ok(RawModeGuard { raw_mode })
     }
 }
 
@@ -46,73 +43,150 @@ impl RawModeGuard {
 // when the RawModeGuard goes out of scope.
 impl Drop for RawModeGuard {
     fn drop(&mut self) {
-        let stdin = io::stdin();
-        let fd = stdin.as_raw_fd();
+        if let Err(err) = write_stdout(b"\x1B[?1006l\x1B[?1000l") {
+            eprintln!("Error disabling mouse reporting: {}", err);
+        }
+        if let Err(err) = write_stdout(b"\x1B[?2004l") {
+            eprintln!("Error disabling bracketed paste: {}", err);
+        }
+        if let Err(err) = write_stdout(b"\x1B[?1004l") {
+            eprintln!("Error disabling focus reporting: {}", err);
+        }
 
         // Restore the original terminal attributes
-        if unsafe { tcsetattr(fd, TCSANOW, &self.original_termios) } != 0 {
-            eprintln!("Error restoring terminal mode: {}", io::Error::last_os_error());
+        if let Err(err) = self.raw_mode.restore() {
+            eprintln!("Error restoring terminal mode: {}", err);
         } else {
             println!("\nOriginal mode restored.");
         }
     }
 }
 
-pub fn run_app_in_raw_mode() {
-    let _guard = match RawModeGuard::enable_raw_mode() {
-        Ok(guard) => guard,
-        Err(err) => {
-            eprintln!("Failed to enable raw mode: {}", err);
-            return;
-        }
-    };
-    
+/// Runs the editor on `paths`, one buffer each and all of them read-only if
+/// `read_only` is set, with the terminal in raw mode. Unrecoverable errors
+/// are returned once the guard has restored the terminal, so the caller can
+/// report them on a sane screen.
+pub fn run_app_in_raw_mode(paths: &[PathBuf], read_only: bool) -> Result<(), EditorError> {
+    let _guard = RawModeGuard::enable_raw_mode()?;
+    let mut state = EditorState::new()?;
+
+    for path in paths {
+        state.open_in_new_buffer(path);
+    }
+    for buffer in &mut state.buffers {
+        buffer.read_only = read_only;
+    }
+    // Start on the first file, like the command line lists them.
+    state.active = 0;
+
+    install_resize_handler()?;
+
     clear_screen();
+    // Keep a startup error (e.g. a bad config file) visible instead of the help.
+    if state.status_msg.is_empty() {
+        state.set_status_message("HELP: i = insert | Ctrl-S = save | Ctrl-Q = quit | Ctrl-F = find".to_string());
+    }
 
-    println!("Type characters. Press 'q' to quit, or hit Ctrl-C/Panic to test Drop guard.");
+    let result = editor_loop(&mut state);
+    state.remember_positions();
+    // After a crash-free session the swap files are no longer needed.
+    if result.is_ok() {
+        state.remove_swap_files();
+    }
 
-    let mut stdin = io::stdin();
-    let mut byte = [0; 1];
+    clear_screen(); // Guard drops after this, mode restored
 
-    loop {
-        if stdin.read_exact(&mut byte).is_ok() {
-            let char_byte = byte[0];
+    if result.is_ok() && state.any_dirty() {
+        println!("Unsaved changes were discarded.");
+    }
+    result
+}
+
+/// Redraws and handles keys until the user quits or the screen can no
+/// longer be written.
+fn editor_loop(state: &mut EditorState) -> Result<(), EditorError> {
+    let mut stdin = platform::input_reader();
+
+    while !state.quit {
+        state.check_resize(get_window_size);
+        state.scroll();
+        state.refresh_screen()?;
+
+        // Reads time out every 100ms, so the screen is redrawn even when
+        // idle, status messages expire on time and auto-save notices the
+        // pause. Prompts read keys themselves, so they are never cut short.
+        let handled = process_available_keys(state, &mut stdin);
+        let now = Instant::now();
+        state.autosave_tick(handled > 0, now);
+        state.swap_tick(handled > 0, now);
+    }
+    Ok(())
+}
 
-            // Echo character back manually
-            io::stdout().write_all(&[char_byte]).unwrap();
-            io::stdout().flush().unwrap();
+/// Queries the terminal for its size, returned as `(rows, cols)`.
+///
+/// Asks the platform first, falling back to moving the cursor to the
+/// bottom-right corner and asking the terminal where it ended up.
+pub fn get_window_size() -> Result<(u16, u16), EditorError> {
+    if let Some(size) = platform::window_size() {
+        return Ok(size);
+    }
 
-            if char_byte == b'q' {
-                clear_screen();
-                break; // Exits loop, guard drops, mode restored
-            }
+    // \x1B[999C - Move cursor right (stops at the right edge)
+    // \x1B[999B - Move cursor down (stops at the bottom edge)
+    write_stdout(b"\x1B[999C\x1B[999B")?;
+    get_cursor_position()
+}
 
-            // Uncomment the following line to simulate a panic:
-            // if char_byte == b'p' {
-            //     panic!("Simulating a panic to test the Drop guard!");
-            // }
+/// Returns whether the terminal was resized since the last call.
+pub fn take_window_resized() -> bool {
+    WINDOW_RESIZED.swap(false, Ordering::SeqCst)
+}
+
+/// Asks the terminal for the cursor position with a Device Status Report.
+fn get_cursor_position() -> Result<(u16, u16), EditorError> {
+    // \x1B[6n - Report cursor position as \x1B[{row};{col}R
+    write_stdout(b"\x1B[6n")?;
+
+    let mut stdin = io::stdin();
+    let mut report = Vec::new();
+    let mut byte = [0; 1];
+
+    while report.len() < 32 {
+        if stdin.read(&mut byte)? != 1 {
+            break;
+        }
+        report.push(byte[0]);
+        if byte[0] == b'R' {
+            break;
         }
     }
+
+    parse_cursor_report(&report)
+        .ok_or_else(|| EditorError::Parse("malformed cursor position report".to_string()))
+}
+
+/// Parses a cursor position report (`\x1B[{row};{col}R`) into `(row, col)`.
+pub fn parse_cursor_report(report: &[u8]) -> Option<(u16, u16)> {
+    let body = report.strip_prefix(b"\x1B[")?.strip_suffix(b"R")?;
+    let body = std::str::from_utf8(body).ok()?;
+    let (rows, cols) = body.split_once(';')?;
+
+    Some((rows.parse().ok()?, cols.parse().ok()?))
 }
 
 pub fn clear_screen() {
     // \x1B[2J - Clear entire screen
     // \x1B[H  - Move cursor to top-left (home)
-    let sequence = b"\x1B[2J\x1B[H";
-    unsafe {
-        libc::write(
-            STDOUT_FILENO,
-            sequence.as_ptr() as *const c_void,
-            sequence.len(),
-        );
-    }
+    // Nothing useful can be done if this fails; the next frame repaints anyway.
+    let _ = write_stdout(b"\x1B[2J\x1B[H");
 }
 
 
-#[cfg(test)]
+#[cfg(all(test, unix))]
 mod panic_tests {
     use super::*;
-    use libc::{tcgetattr, termios, STDIN_FILENO};
+    use libc::{tcgetattr, termios, ECHO, ICANON, STDIN_FILENO};
     use std::panic;
     use std::mem;
 
@@ -191,3 +265,41 @@ mod panic_tests {
 
 }
 
+#[cfg(test)]
+mod window_size_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cursor_report() {
+        assert_eq!(parse_cursor_report(b"\x1B[24;80R"), Some((24, 80)));
+    }
+
+    #[test]
+    fn test_parse_cursor_report_rejects_malformed_input() {
+        assert_eq!(parse_cursor_report(b"\x1B[24;80"), None);
+        assert_eq!(parse_cursor_report(b"24;80R"), None);
+        assert_eq!(parse_cursor_report(b"\x1B[24R"), None);
+        assert_eq!(parse_cursor_report(b"\x1B[a;bR"), None);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod resize_tests {
+    use super::*;
+
+    #[test]
+    fn test_sigwinch_flags_resize_for_main_loop() {
+        install_resize_handler().unwrap();
+        let mut state = EditorState::with_size(10, 20);
+
+        // Nothing happens until a resize has been signalled.
+        assert!(!state.check_resize(|| Ok((30, 100))));
+        assert_eq!((state.screen_rows, state.screen_cols), (10, 20));
+
+        assert_eq!(unsafe { libc::raise(libc::SIGWINCH) }, 0);
+
+        assert!(state.check_resize(|| Ok((30, 100))));
+        assert_eq!((state.screen_rows, state.screen_cols), (28, 100));
+        assert!(!take_window_resized());
+    }
+}
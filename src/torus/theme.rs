@@ -0,0 +1,337 @@
+use std::path::Path;
+use std::{env, fs};
+
+use serde::Deserialize;
+
+use super::error::EditorError;
+use super::highlight::Highlight;
+
+/// Names of the 16 ANSI colours, in SGR order.
+const ANSI_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright-black",
+    "bright-red",
+    "bright-green",
+    "bright-yellow",
+    "bright-blue",
+    "bright-magenta",
+    "bright-cyan",
+    "bright-white",
+];
+
+/// The xterm values of the 16 ANSI colours, for picking the closest one to
+/// a 24-bit colour.
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel values of the 6x6x6 colour cube in the 256-colour palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// How many colours the terminal can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Works out the depth from `$COLORTERM` and `$TERM`, assuming only the
+    /// 16 ANSI colours unless either says otherwise.
+    pub fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        ColorDepth::from_env(&colorterm, &term)
+    }
+
+    fn from_env(colorterm: &str, term: &str) -> ColorDepth {
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+/// A colour in a theme, written as `"default"`, an ANSI colour name such as
+/// `"bright-blue"`, or `"#rrggbb"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Color {
+    /// The terminal's own foreground or background.
+    Default,
+    /// One of the 16 ANSI colours, by index into `ANSI_NAMES`.
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Color, String> {
+        if name == "default" {
+            return Ok(Color::Default);
+        }
+        if let Some(index) = ANSI_NAMES.iter().position(|&ansi| ansi == name) {
+            return Ok(Color::Ansi(index as u8));
+        }
+        let hex = name.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.is_ascii());
+        let channel = |i: usize| hex.and_then(|hex| u8::from_str_radix(&hex[i..i + 2], 16).ok());
+        match (channel(0), channel(2), channel(4)) {
+            (Some(r), Some(g), Some(b)) => Ok(Color::Rgb(r, g, b)),
+            _ => Err(format!("not a colour: {}", name)),
+        }
+    }
+}
+
+impl Color {
+    /// SGR sequence setting this colour as the foreground, or the background
+    /// if `background` is set. A 24-bit colour becomes the closest one the
+    /// terminal's `depth` has.
+    pub fn sgr(self, background: bool, depth: ColorDepth) -> String {
+        let ansi = |index: u8| match (index, background) {
+            (0..=7, false) => 30 + index,
+            (_, false) => 90 + index - 8,
+            (0..=7, true) => 40 + index,
+            (_, true) => 100 + index - 8,
+        };
+        match self {
+            Color::Default if background => "\x1B[49m".to_string(),
+            Color::Default => "\x1B[39m".to_string(),
+            Color::Ansi(index) => format!("\x1B[{}m", ansi(index)),
+            // \x1B[38;2;r;g;bm - 24-bit foreground, 48 for the background
+            Color::Rgb(r, g, b) if depth == ColorDepth::TrueColor => {
+                format!("\x1B[{};2;{};{};{}m", if background { 48 } else { 38 }, r, g, b)
+            }
+            // \x1B[38;5;nm - 256-colour foreground, 48 for the background
+            Color::Rgb(r, g, b) if depth == ColorDepth::Ansi256 => {
+                format!("\x1B[{};5;{}m", if background { 48 } else { 38 }, closest_256(r, g, b))
+            }
+            Color::Rgb(r, g, b) => format!("\x1B[{}m", ansi(closest_ansi(r, g, b))),
+        }
+    }
+}
+
+/// Squared distance between two colours.
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> i32 {
+    let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Index of the ANSI colour nearest to `(r, g, b)`.
+fn closest_ansi(r: u8, g: u8, b: u8) -> u8 {
+    let (index, _) = ANSI_RGB
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, &ansi)| distance(ansi, (r, g, b)))
+        .expect("palette is not empty");
+    index as u8
+}
+
+/// Index in the 256-colour palette of the colour nearest to `(r, g, b)`:
+/// the closest point of the 6x6x6 cube (16-231) or of the grey ramp
+/// (232-255). The first 16 are left out, since terminals set them freely.
+pub fn closest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |value: u8| {
+        (0..CUBE_LEVELS.len()).min_by_key(|&i| (i32::from(CUBE_LEVELS[i]) - i32::from(value)).abs()).unwrap_or(0)
+    };
+    let (ri, gi, bi) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = 16 + 36 * ri + 6 * gi + bi;
+
+    // The ramp runs from 8 to 238 in steps of 10.
+    let average = (u32::from(r) + u32::from(g) + u32::from(b)) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey = 8 + 10 * step;
+
+    if distance((grey, grey, grey), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + step
+    } else {
+        cube_index as u8
+    }
+}
+
+/// Colours of the highlight classes, read from a theme file. The syntax
+/// classes set the text colour, the others the cell background. Plain text
+/// keeps the terminal's colours, and selections and control characters are
+/// always in reverse video, which reads well with any colours.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub number: Color,
+    pub string: Color,
+    pub comment: Color,
+    pub keyword: Color,
+    pub match_bracket: Color,
+    pub trailing_whitespace: Color,
+    pub search_match: Color,
+    pub current_match: Color,
+    pub ruler: Color,
+    pub current_line: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            number: Color::Ansi(1),
+            string: Color::Ansi(5),
+            comment: Color::Ansi(6),
+            keyword: Color::Ansi(3),
+            match_bracket: Color::Ansi(6),
+            trailing_whitespace: Color::Ansi(1),
+            search_match: Color::Ansi(4),
+            current_match: Color::Ansi(12),
+            ruler: Color::Ansi(8),
+            current_line: Color::Rgb(0x30, 0x30, 0x30),
+        }
+    }
+}
+
+impl Theme {
+    /// Parses a theme file's contents. Missing classes keep the built-in
+    /// theme's colours.
+    pub fn parse(text: &str) -> Result<Theme, EditorError> {
+        toml::from_str(text).map_err(|err| EditorError::Parse(err.message().to_string()))
+    }
+
+    /// Reads and parses the theme file at `path`.
+    pub fn load(path: &Path) -> Result<Theme, EditorError> {
+        let text = fs::read_to_string(path)?;
+        Theme::parse(&text).map_err(|err| EditorError::Parse(format!("{}: {}", path.display(), err)))
+    }
+}
+
+/// The SGR sequence that starts each highlight class, worked out once from
+/// a theme for the terminal's colour support.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    sequences: Vec<String>,
+}
+
+impl Palette {
+    pub fn new(theme: &Theme, depth: ColorDepth) -> Palette {
+        let sequences = Highlight::ALL
+            .iter()
+            .map(|&class| {
+                let (color, background) = match class {
+                    Highlight::Normal => (Color::Default, false),
+                    Highlight::Number => (theme.number, false),
+                    Highlight::String => (theme.string, false),
+                    Highlight::Comment => (theme.comment, false),
+                    Highlight::Keyword => (theme.keyword, false),
+                    // \x1B[7m - Reverse video
+                    Highlight::Selection | Highlight::Control => return "\x1B[7m".to_string(),
+                    Highlight::MatchBracket => (theme.match_bracket, true),
+                    Highlight::TrailingWhitespace => (theme.trailing_whitespace, true),
+                    Highlight::SearchMatch => (theme.search_match, true),
+                    Highlight::CurrentMatch => (theme.current_match, true),
+                    Highlight::Ruler => (theme.ruler, true),
+                    Highlight::CurrentLine => (theme.current_line, true),
+                };
+                color.sgr(background, depth)
+            })
+            .collect();
+        Palette { sequences }
+    }
+
+    /// The sequence that starts drawing in `class`.
+    pub fn sgr(&self, class: Highlight) -> &str {
+        &self.sequences[class as usize]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::new(&Theme::default(), ColorDepth::Ansi16)
+    }
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme() {
+        let theme = Theme::parse("keyword = \"#ff8800\"\ncomment = \"bright-black\"\nnumber = \"default\"\n").unwrap();
+
+        assert_eq!(theme.keyword, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.comment, Color::Ansi(8));
+        assert_eq!(theme.number, Color::Default);
+        assert_eq!(theme.string, Theme::default().string);
+    }
+
+    #[test]
+    fn test_malformed_theme_is_an_error() {
+        assert!(Theme::parse("keyword = \"#ff88\"").is_err());
+        assert!(Theme::parse("keyword = \"orange\"").is_err());
+        assert!(Theme::parse("unknown = \"red\"").is_err());
+    }
+
+    #[test]
+    fn test_truecolor_foreground_sgr() {
+        let color = Color::Rgb(255, 136, 0);
+
+        assert_eq!(color.sgr(false, ColorDepth::TrueColor), "\x1B[38;2;255;136;0m");
+        assert_eq!(color.sgr(true, ColorDepth::TrueColor), "\x1B[48;2;255;136;0m");
+        assert_eq!(color.sgr(false, ColorDepth::Ansi256), "\x1B[38;5;208m");
+    }
+
+    #[test]
+    fn test_rgb_falls_back_to_closest_ansi_colour() {
+        assert_eq!(Color::Rgb(250, 10, 10).sgr(false, ColorDepth::Ansi16), "\x1B[91m");
+        assert_eq!(Color::Rgb(0, 0, 200).sgr(true, ColorDepth::Ansi16), "\x1B[44m");
+    }
+
+    #[test]
+    fn test_rgb_quantizes_to_256_colours() {
+        assert_eq!(closest_256(0, 0, 0), 16);
+        assert_eq!(closest_256(255, 0, 0), 196);
+        assert_eq!(closest_256(0, 0, 255), 21);
+        assert_eq!(closest_256(255, 255, 255), 231);
+        assert_eq!(closest_256(95, 135, 175), 67);
+        assert_eq!(closest_256(128, 128, 128), 244);
+        assert_eq!(closest_256(30, 30, 32), 234);
+    }
+
+    #[test]
+    fn test_color_depth_from_environment() {
+        assert_eq!(ColorDepth::from_env("truecolor", "xterm"), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env("24bit", ""), ColorDepth::TrueColor);
+        assert_eq!(ColorDepth::from_env("", "xterm-256color"), ColorDepth::Ansi256);
+        assert_eq!(ColorDepth::from_env("", "vt100"), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn test_default_palette_uses_ansi_codes() {
+        let palette = Palette::default();
+
+        assert_eq!(palette.sgr(Highlight::Keyword), "\x1B[33m");
+        assert_eq!(palette.sgr(Highlight::CurrentMatch), "\x1B[104m");
+        assert_eq!(palette.sgr(Highlight::Selection), "\x1B[7m");
+    }
+}
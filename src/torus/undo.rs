@@ -0,0 +1,313 @@
+use std::ops::Range;
+
+use super::editor::{byte_index, EditorState};
+
+/// A single reversible change to the rows of a buffer. Positions are
+/// `(row, col)` pairs with char columns, like the cursor's.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditOp {
+    InsertChar { at: (usize, usize), c: char },
+    DeleteChar { at: (usize, usize), c: char },
+    /// Moves the text of a row from `at` onwards onto a new row below it.
+    SplitLine { at: (usize, usize) },
+    /// Appends the row below `at.0` onto it; `at.1` is the joined row's
+    /// length beforehand, so the join can be split again.
+    JoinLine { at: (usize, usize) },
+    InsertRow { row: usize, text: String },
+    RemoveRow { row: usize, text: String },
+}
+
+impl EditOp {
+    /// Returns the operation that undoes this one.
+    pub fn inverse(&self) -> EditOp {
+        match self.clone() {
+            EditOp::InsertChar { at, c } => EditOp::DeleteChar { at, c },
+            EditOp::DeleteChar { at, c } => EditOp::InsertChar { at, c },
+            EditOp::SplitLine { at } => EditOp::JoinLine { at },
+            EditOp::JoinLine { at } => EditOp::SplitLine { at },
+            EditOp::InsertRow { row, text } => EditOp::RemoveRow { row, text },
+            EditOp::RemoveRow { row, text } => EditOp::InsertRow { row, text },
+        }
+    }
+
+    /// Applies the operation to `rows`.
+    pub fn apply(&self, rows: &mut Vec<String>) {
+        match *self {
+            EditOp::InsertChar { at: (y, x), c } => {
+                let row = &mut rows[y];
+                row.insert(byte_index(row, x), c);
+            }
+            EditOp::DeleteChar { at: (y, x), .. } => {
+                let row = &mut rows[y];
+                row.remove(byte_index(row, x));
+            }
+            EditOp::SplitLine { at: (y, x) } => {
+                let row = &mut rows[y];
+                let tail = row.split_off(byte_index(row, x));
+                rows.insert(y + 1, tail);
+            }
+            EditOp::JoinLine { at: (y, _) } => {
+                let next = rows.remove(y + 1);
+                rows[y].push_str(&next);
+            }
+            EditOp::InsertRow { row, ref text } => rows.insert(row, text.clone()),
+            EditOp::RemoveRow { row, .. } => {
+                rows.remove(row);
+            }
+        }
+    }
+}
+
+/// A group of operations undone and redone together, with the cursor
+/// positions to restore on either side of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoUnit {
+    pub ops: Vec<EditOp>,
+    pub cursor_before: (usize, usize),
+    pub cursor_after: (usize, usize),
+}
+
+impl UndoUnit {
+    /// True if `op`, starting at `cursor`, continues the typing in this unit.
+    fn continues_typing(&self, op: &EditOp, cursor: (usize, usize)) -> bool {
+        matches!(op, EditOp::InsertChar { .. })
+            && self.cursor_after == cursor
+            && self
+                .ops
+                .iter()
+                .all(|op| matches!(op, EditOp::InsertChar { .. } | EditOp::InsertRow { .. }))
+    }
+}
+
+/// Undo and redo history of a buffer.
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    pub undo: Vec<UndoUnit>,
+    pub redo: Vec<UndoUnit>,
+}
+
+impl UndoStack {
+    /// Records operations that together moved the cursor from `before` to
+    /// `after`. A character typed right where the previous one left the
+    /// cursor joins its unit, so a typed word undoes in one step. Any new
+    /// edit discards the redo history.
+    pub fn record(&mut self, ops: Vec<EditOp>, before: (usize, usize), after: (usize, usize)) {
+        self.redo.clear();
+
+        if let [op] = ops.as_slice()
+            && let Some(unit) = self.undo.last_mut()
+            && unit.continues_typing(op, before)
+        {
+            unit.ops.extend(ops);
+            unit.cursor_after = after;
+            return;
+        }
+
+        self.undo.push(UndoUnit {
+            ops,
+            cursor_before: before,
+            cursor_after: after,
+        });
+    }
+}
+
+impl EditorState {
+    /// Applies `op` to the buffer and records it in the undo history.
+    /// `after` is where the cursor ends up.
+    pub fn edit(&mut self, op: EditOp, after: (usize, usize)) {
+        self.edit_all(vec![op], after);
+    }
+
+    /// Applies `ops` in order and records them as a single undo unit.
+    /// Nothing happens to a read-only buffer.
+    pub fn edit_all(&mut self, ops: Vec<EditOp>, after: (usize, usize)) {
+        if !self.check_writable() {
+            return;
+        }
+        let before = (self.cursor_y, self.cursor_x);
+        for op in &ops {
+            self.apply_op(op);
+        }
+        self.undo_stack.record(ops, before, after);
+        (self.cursor_y, self.cursor_x) = after;
+    }
+
+    /// Replaces the rows in `range` with `rows` as one undo unit, leaving
+    /// the cursor at the start of the first of them.
+    pub fn replace_rows(&mut self, range: Range<usize>, rows: Vec<String>) {
+        let first = range.start;
+        let mut ops: Vec<EditOp> = range.rev().map(|row| EditOp::RemoveRow { row, text: self.rows[row].clone() }).collect();
+        ops.extend(rows.into_iter().enumerate().map(|(i, text)| EditOp::InsertRow { row: first + i, text }));
+        self.edit_all(ops, (first, 0));
+    }
+
+    /// True if the active buffer may be edited; otherwise says why not in
+    /// the message bar.
+    pub fn check_writable(&mut self) -> bool {
+        if self.read_only {
+            self.set_status_message("File is read-only".to_string());
+        }
+        !self.read_only
+    }
+
+    /// Applies `op` without recording it.
+    fn apply_op(&mut self, op: &EditOp) {
+        op.apply(&mut self.rows);
+        self.note_edit(op);
+        self.dirty = true;
+    }
+
+    /// Reverts the most recent undo unit and restores the cursor.
+    pub fn undo(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(unit) = self.undo_stack.undo.pop() else {
+            self.set_status_message("Nothing to undo".to_string());
+            return;
+        };
+
+        for op in unit.ops.iter().rev() {
+            self.apply_op(&op.inverse());
+        }
+        (self.cursor_y, self.cursor_x) = unit.cursor_before;
+        self.undo_stack.redo.push(unit);
+    }
+
+    /// Re-applies the most recently undone unit.
+    pub fn redo(&mut self) {
+        if !self.check_writable() {
+            return;
+        }
+        let Some(unit) = self.undo_stack.redo.pop() else {
+            self.set_status_message("Nothing to redo".to_string());
+            return;
+        };
+
+        for op in &unit.ops {
+            self.apply_op(op);
+        }
+        (self.cursor_y, self.cursor_x) = unit.cursor_after;
+        self.undo_stack.undo.push(unit);
+    }
+}
+
+#[cfg(test)]
+mod undo_tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_removes_typed_word_in_one_step() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["x".to_string()];
+        state.cursor_x = 1;
+
+        for c in "abc".chars() {
+            state.insert_char(c);
+        }
+        assert_eq!(state.rows, vec!["xabc"]);
+
+        state.undo();
+
+        assert_eq!(state.rows, vec!["x"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (1, 0));
+    }
+
+    #[test]
+    fn test_redo_reapplies_undone_edit() {
+        let mut state = EditorState::with_size(24, 80);
+
+        for c in "hi".chars() {
+            state.insert_char(c);
+        }
+        state.insert_newline();
+        state.undo();
+        assert_eq!(state.rows, vec!["hi"]);
+
+        state.redo();
+        assert_eq!(state.rows, vec!["hi", ""]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+
+        state.undo();
+        state.undo();
+        assert!(state.rows.is_empty());
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 0));
+    }
+
+    #[test]
+    fn test_undo_join_and_delete() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.cursor_y = 1;
+
+        state.delete_char();
+        state.delete_char();
+        assert_eq!(state.rows, vec!["acd"]);
+
+        state.undo();
+        assert_eq!(state.rows, vec!["abcd"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (2, 0));
+        state.undo();
+        assert_eq!(state.rows, vec!["ab", "cd"]);
+        assert_eq!((state.cursor_x, state.cursor_y), (0, 1));
+    }
+
+    #[test]
+    fn test_moving_the_cursor_starts_a_new_unit() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["xy".to_string()];
+
+        state.insert_char('a');
+        state.cursor_x = 3;
+        state.insert_char('b');
+        state.undo();
+
+        assert_eq!(state.rows, vec!["axy"]);
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo() {
+        let mut state = EditorState::with_size(24, 80);
+
+        state.insert_char('a');
+        state.undo();
+        state.insert_char('b');
+        state.redo();
+
+        assert_eq!(state.rows, vec!["b"]);
+    }
+
+    #[test]
+    fn test_read_only_buffer_ignores_edits() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.read_only = true;
+        state.cursor_x = 1;
+
+        state.insert_char('x');
+        state.delete_char();
+        state.cursor_y = 1;
+        state.cursor_x = 0;
+        state.delete_char();
+
+        assert_eq!(state.rows, vec!["ab", "cd"]);
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 0));
+        assert!(!state.dirty);
+        assert_eq!(state.status_msg, "File is read-only");
+    }
+
+    #[test]
+    fn test_delete_forward_in_read_only_buffer_keeps_cursor() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["ab".to_string(), "cd".to_string()];
+        state.read_only = true;
+
+        state.delete_char_forward();
+        state.cursor_x = 2;
+        state.delete_char_forward();
+
+        assert_eq!(state.rows, vec!["ab", "cd"]);
+        assert_eq!((state.cursor_y, state.cursor_x), (0, 2));
+        assert_eq!(state.status_msg, "File is read-only");
+    }
+}
@@ -0,0 +1,74 @@
+use super::editor::{char_count, EditorState};
+use super::undo::EditOp;
+
+/// True for the characters counted as trailing whitespace.
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// `row` without its trailing spaces and tabs.
+pub fn strip_trailing_whitespace(row: &str) -> &str {
+    row.trim_end_matches(is_blank)
+}
+
+/// Char column at which the trailing whitespace of `row` starts; the row
+/// length if there is none.
+pub fn trailing_whitespace_start(row: &str) -> usize {
+    char_count(strip_trailing_whitespace(row))
+}
+
+impl EditorState {
+    /// Column from which row `y` of the active buffer counts as trailing
+    /// whitespace. On the cursor's row, whitespace before the cursor is
+    /// spared, since it may be a space the user is about to type after.
+    pub fn trailing_whitespace_from(&self, y: usize) -> usize {
+        let start = self.row(y).map_or(0, trailing_whitespace_start);
+        if y == self.cursor_y { start.max(self.cursor_x) } else { start }
+    }
+
+    /// Removes trailing whitespace from every row as one undo unit.
+    pub fn strip_trailing_whitespace(&mut self) {
+        let mut ops = Vec::new();
+        for (y, row) in self.rows.iter().enumerate() {
+            let from = self.trailing_whitespace_from(y);
+            // Delete from the end so earlier columns stay valid.
+            for (x, c) in row.chars().enumerate().skip(from).collect::<Vec<_>>().into_iter().rev() {
+                ops.push(EditOp::DeleteChar { at: (y, x), c });
+            }
+        }
+
+        if !ops.is_empty() {
+            let cursor = (self.cursor_y, self.cursor_x);
+            self.edit_all(ops, cursor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod whitespace_tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_mixed_trailing_whitespace() {
+        assert_eq!(strip_trailing_whitespace("code \t \t"), "code");
+        assert_eq!(strip_trailing_whitespace("\tindented  "), "\tindented");
+        assert_eq!(strip_trailing_whitespace(" \t "), "");
+        assert_eq!(strip_trailing_whitespace("clean"), "clean");
+        assert_eq!(trailing_whitespace_start("ab \t"), 2);
+    }
+
+    #[test]
+    fn test_strip_spares_whitespace_before_cursor() {
+        let mut state = EditorState::with_size(24, 80);
+        state.rows = vec!["a  ".to_string(), "b \t".to_string()];
+        state.cursor_y = 1;
+        state.cursor_x = 2;
+
+        state.strip_trailing_whitespace();
+        assert_eq!(state.rows, vec!["a", "b "]);
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 2));
+
+        state.undo();
+        assert_eq!(state.rows, vec!["a  ", "b \t"]);
+    }
+}
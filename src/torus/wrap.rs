@@ -0,0 +1,192 @@
+use super::buffer::Buffer;
+use super::editor::EditorState;
+use super::render::{char_width, render_row_with};
+
+/// One screen row of a pane: the part of a buffer row starting at render
+/// column `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayLine {
+    pub file_row: usize,
+    pub start: usize,
+    /// False for the continuation rows of a wrapped line.
+    pub first: bool,
+}
+
+/// Render columns at which each screen row of `rendered` starts when it is
+/// wrapped at `width` columns. The first is always 0, and a wide character
+/// that would be cut by the edge moves to the next row whole.
+pub fn wrap_starts(rendered: &str, width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    let mut col = 0;
+    for c in rendered.chars() {
+        let w = char_width(c);
+        if col > start && col + w > start + width {
+            start = col;
+            starts.push(start);
+        }
+        col += w;
+    }
+    starts
+}
+
+/// Which of the screen rows given by `starts` render column `rx` is on,
+/// and its column there. A cursor just past the end of a full row stays on
+/// that row's last cell rather than starting a row of its own.
+pub fn wrap_position(starts: &[usize], rx: usize, width: usize) -> (usize, usize) {
+    let row = starts.iter().rposition(|&start| start <= rx).unwrap_or(0);
+    (row, (rx - starts[row]).min(width.saturating_sub(1)))
+}
+
+impl EditorState {
+    /// Where each screen row of `buffer` starts, as wrapped at `width`.
+    fn row_wrap_starts(&self, buffer: &Buffer, file_row: usize, width: usize) -> Vec<usize> {
+        let row = &buffer.rows[file_row];
+        wrap_starts(&render_row_with(row, self.config.tab_width, self.show_whitespace), width)
+    }
+
+    /// The buffer rows, or parts of them, shown on the first `height` rows
+    /// of a pane `width` columns wide, from `row_offset` down. Shorter than
+    /// `height` once the buffer runs out.
+    pub fn display_lines(&self, buffer: &Buffer, height: usize, width: usize) -> Vec<DisplayLine> {
+        let mut lines = Vec::with_capacity(height);
+        for file_row in buffer.row_offset..buffer.rows.len() {
+            if lines.len() >= height {
+                break;
+            }
+            if !self.wrap_lines {
+                lines.push(DisplayLine { file_row, start: buffer.col_offset, first: true });
+                continue;
+            }
+            for (i, start) in self.row_wrap_starts(buffer, file_row, width).into_iter().enumerate() {
+                lines.push(DisplayLine { file_row, start, first: i == 0 });
+            }
+        }
+        lines.truncate(height);
+        lines
+    }
+
+    /// Screen rows that buffer row `file_row` of the active buffer takes
+    /// when wrapped.
+    fn wrapped_height(&self, file_row: usize) -> usize {
+        self.row_wrap_starts(self, file_row, self.text_cols()).len()
+    }
+
+    /// The cursor's row and column in the focused pane when lines wrap.
+    pub fn wrapped_cursor_position(&self) -> (usize, usize) {
+        let above: usize = (self.row_offset..self.cursor_y).map(|y| self.wrapped_height(y)).sum();
+        if self.cursor_y >= self.num_rows() {
+            return (above, 0);
+        }
+        let starts = self.row_wrap_starts(self, self.cursor_y, self.text_cols());
+        let (row, col) = wrap_position(&starts, self.cursor_rx(), self.text_cols());
+        (above + row, col)
+    }
+
+    /// Like `scroll` for wrapped lines, counting screen rows rather than
+    /// buffer rows. The view still starts at the top of a buffer row, so a
+    /// row taller than the pane shows only its top.
+    pub fn scroll_wrapped(&mut self) {
+        self.col_offset = 0;
+        let text_rows = self.text_rows();
+        let margin = self.config.scroll_margin.min(text_rows.saturating_sub(1) / 2);
+        if self.cursor_y < self.row_offset + margin {
+            self.row_offset = self.cursor_y.saturating_sub(margin);
+        }
+        if self.cursor_y >= self.num_rows() {
+            return;
+        }
+
+        // Every row takes at least one screen row, which bounds how far up
+        // the view can start before counting them one by one.
+        let last = (self.cursor_y + margin).min(self.num_rows() - 1);
+        self.row_offset = self.row_offset.max((last + 1).saturating_sub(text_rows));
+
+        let starts = self.row_wrap_starts(self, self.cursor_y, self.text_cols());
+        let (cursor_row, _) = wrap_position(&starts, self.cursor_rx(), self.text_cols());
+        let below: usize = (self.cursor_y + 1..=last).map(|y| self.wrapped_height(y)).sum();
+        let mut needed = (self.row_offset..self.cursor_y).map(|y| self.wrapped_height(y)).sum::<usize>()
+            + cursor_row
+            + 1
+            + below;
+        while needed > text_rows && self.row_offset < self.cursor_y {
+            needed -= self.wrapped_height(self.row_offset);
+            self.row_offset += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod wrap_tests {
+    use super::*;
+    use crate::torus::render::AppendBuffer;
+
+    #[test]
+    fn test_line_wrapping_three_times_maps_to_four_display_rows() {
+        let line = "abcdefghij".repeat(3) + "xyz";
+        let starts = wrap_starts(&line, 10);
+
+        assert_eq!(starts, vec![0, 10, 20, 30]);
+        assert_eq!(wrap_position(&starts, 0, 10), (0, 0));
+        assert_eq!(wrap_position(&starts, 9, 10), (0, 9));
+        assert_eq!(wrap_position(&starts, 10, 10), (1, 0));
+        assert_eq!(wrap_position(&starts, 25, 10), (2, 5));
+        assert_eq!(wrap_position(&starts, 33, 10), (3, 3));
+    }
+
+    #[test]
+    fn test_wide_char_moves_to_next_row_whole() {
+        assert_eq!(wrap_starts("abc中", 4), vec![0, 3]);
+        assert_eq!(wrap_starts("", 4), vec![0]);
+        // A cursor after a full last row stays on its last cell.
+        assert_eq!(wrap_position(&[0], 4, 4), (0, 3));
+    }
+
+    #[test]
+    fn test_wrapped_rows_drawn_with_blank_continuation_gutter() {
+        let mut state = EditorState::with_size(4, 6);
+        state.wrap_lines = true;
+        state.show_line_numbers = true;
+        state.rows = vec!["abcdefghij".to_string(), "k".to_string()];
+
+        let mut buf = AppendBuffer::new();
+        state.draw_buffer_rows(&mut buf, state.active, state.text_rows());
+
+        let out = String::from_utf8_lossy(buf.as_bytes()).into_owned();
+        assert_eq!(out, "1 abcd\x1B[K\r\n  efgh\x1B[K\r\n  ij\x1B[K\r\n2 k\x1B[K\r\n");
+    }
+
+    #[test]
+    fn test_cursor_and_scroll_count_display_rows() {
+        let mut state = EditorState::with_size(3, 10);
+        state.wrap_lines = true;
+        state.rows = vec!["x".repeat(25), "short".to_string(), "end".to_string()];
+        (state.cursor_y, state.cursor_x) = (0, 22);
+        assert_eq!(state.wrapped_cursor_position(), (2, 2));
+
+        state.cursor_y = 1;
+        state.cursor_x = 0;
+        state.scroll();
+        assert_eq!(state.row_offset, 1);
+        assert_eq!(state.wrapped_cursor_position(), (0, 0));
+
+        state.cursor_y = 0;
+        state.cursor_x = 25;
+        state.scroll();
+        assert_eq!(state.row_offset, 0);
+        assert_eq!(state.wrapped_cursor_position(), (2, 5));
+    }
+
+    #[test]
+    fn test_click_on_continuation_row() {
+        let mut state = EditorState::with_size(5, 10);
+        state.wrap_lines = true;
+        state.rows = vec!["0123456789abcdef".to_string(), "z".to_string()];
+
+        state.click(3, 2);
+        assert_eq!((state.cursor_y, state.cursor_x), (0, 12));
+
+        state.click(1, 3);
+        assert_eq!((state.cursor_y, state.cursor_x), (1, 0));
+    }
+}